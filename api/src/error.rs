@@ -0,0 +1,71 @@
+use axum::{
+    extract::rejection::{JsonRejection, PathRejection, QueryRejection},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use models::api::error::{ApiError, ErrorCode};
+
+/// The error type of every handler, rendered as an [`ApiError`] body.
+#[derive(Debug)]
+pub struct AppError(pub ApiError);
+
+impl IntoResponse for AppError {
+    fn into_response(self) -> Response {
+        let status =
+            StatusCode::from_u16(self.0.code.status()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+
+        (status, Json(self.0)).into_response()
+    }
+}
+
+impl From<ApiError> for AppError {
+    fn from(value: ApiError) -> Self {
+        Self(value)
+    }
+}
+
+impl From<sqlx::Error> for AppError {
+    fn from(value: sqlx::Error) -> Self {
+        match value {
+            sqlx::Error::RowNotFound => Self(ApiError::not_found("Not found")),
+            sqlx::Error::Database(err) if err.is_unique_violation() => {
+                Self(ApiError::conflict("Already exists"))
+            }
+            err => {
+                tracing::error!("Database error: {err}");
+                Self(ApiError::internal())
+            }
+        }
+    }
+}
+
+impl From<anyhow::Error> for AppError {
+    fn from(value: anyhow::Error) -> Self {
+        tracing::error!("Internal error: {value}");
+        Self(ApiError::internal())
+    }
+}
+
+impl From<JsonRejection> for AppError {
+    fn from(value: JsonRejection) -> Self {
+        let code = match value {
+            JsonRejection::JsonDataError(_) => ErrorCode::ValidationFailed,
+            _ => ErrorCode::BadRequest,
+        };
+
+        Self(ApiError::new(code, value.body_text()))
+    }
+}
+
+impl From<PathRejection> for AppError {
+    fn from(value: PathRejection) -> Self {
+        Self(ApiError::bad_request(value.body_text()))
+    }
+}
+
+impl From<QueryRejection> for AppError {
+    fn from(value: QueryRejection) -> Self {
+        Self(ApiError::bad_request(value.body_text()))
+    }
+}
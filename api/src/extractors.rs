@@ -0,0 +1,179 @@
+use crate::{error::AppError, tokens::TokenKeys};
+use axum::{
+    async_trait,
+    extract::{FromRequest, FromRequestParts},
+    http::{header::AUTHORIZATION, request::Parts},
+    response::{IntoResponse, Response},
+    Extension,
+};
+use models::{
+    api::{error::ApiError, users::Role},
+    data::servers::ServerKey,
+};
+use serde::Serialize;
+use sqlx::{query_as, query_scalar, PgPool};
+use std::{marker::PhantomData, str::FromStr, sync::Arc};
+use uuid::Uuid;
+
+/// [`axum::Json`] rejecting invalid bodies with an [`AppError`].
+#[derive(FromRequest)]
+#[from_request(via(axum::Json), rejection(AppError))]
+pub struct Json<T>(pub T);
+
+impl<T: Serialize> IntoResponse for Json<T> {
+    fn into_response(self) -> Response {
+        axum::Json(self.0).into_response()
+    }
+}
+
+/// [`axum::extract::Path`] rejecting invalid parameters with an [`AppError`].
+#[derive(FromRequestParts)]
+#[from_request(via(axum::extract::Path), rejection(AppError))]
+pub struct Path<T>(pub T);
+
+/// [`axum::extract::Query`] rejecting invalid query strings with an [`AppError`].
+#[derive(FromRequestParts)]
+#[from_request(via(axum::extract::Query), rejection(AppError))]
+pub struct Query<T>(pub T);
+
+/// The user and session owning the verified bearer token of the request.
+///
+/// Tokens belonging to a revoked or expired session are rejected.
+pub struct AuthUser {
+    pub user_id: Uuid,
+    pub session_id: Uuid,
+    /// The current role of the user, which may have changed since the token was issued
+    pub role: Role,
+}
+
+#[async_trait]
+impl<S> FromRequestParts<S> for AuthUser
+where
+    S: Send + Sync,
+{
+    type Rejection = AppError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let Extension(keys) = Extension::<Arc<TokenKeys>>::from_request_parts(parts, state)
+            .await
+            .map_err(|_| ApiError::internal())?;
+        let Extension(pool) = Extension::<PgPool>::from_request_parts(parts, state)
+            .await
+            .map_err(|_| ApiError::internal())?;
+
+        let token = bearer_token(parts).ok_or(ApiError::unauthorized("Missing bearer token"))?;
+
+        let claims = keys
+            .verify_access_token(token)
+            .map_err(|_| ApiError::unauthorized("Invalid or expired token"))?;
+
+        let role: Option<Role> = query_scalar(
+            "SELECT users.role FROM sessions JOIN users ON users.id = sessions.user_id \
+            WHERE sessions.id = $1 AND sessions.user_id = $2 AND sessions.revoked_at IS NULL AND sessions.expires_at > now();",
+        )
+        .bind(claims.session_id)
+        .bind(claims.user_id)
+        .fetch_optional(&pool)
+        .await?;
+
+        let role = role.ok_or(ApiError::unauthorized("Session has ended"))?;
+
+        Ok(Self {
+            user_id: claims.user_id,
+            session_id: claims.session_id,
+            role,
+        })
+    }
+}
+
+/// A role [`RequireRole`] can ask for.
+pub trait MinimumRole {
+    const ROLE: Role;
+}
+
+pub struct Moderator;
+
+impl MinimumRole for Moderator {
+    const ROLE: Role = Role::Moderator;
+}
+
+pub struct Admin;
+
+impl MinimumRole for Admin {
+    const ROLE: Role = Role::Admin;
+}
+
+/// An [`AuthUser`] with at least the role `R`.
+pub struct RequireRole<R> {
+    pub user: AuthUser,
+    role: PhantomData<R>,
+}
+
+#[async_trait]
+impl<S, R> FromRequestParts<S> for RequireRole<R>
+where
+    S: Send + Sync,
+    R: MinimumRole,
+{
+    type Rejection = AppError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let user = AuthUser::from_request_parts(parts, state).await?;
+
+        if user.role < R::ROLE {
+            return Err(
+                ApiError::forbidden(format!("The {} role is required", R::ROLE.as_str())).into(),
+            );
+        }
+
+        Ok(Self {
+            user,
+            role: PhantomData,
+        })
+    }
+}
+
+/// The server owning the api key sent as bearer token of the request.
+pub struct AuthServer(pub models::data::servers::Server);
+
+#[async_trait]
+impl<S> FromRequestParts<S> for AuthServer
+where
+    S: Send + Sync,
+{
+    type Rejection = AppError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let Extension(pool) = Extension::<PgPool>::from_request_parts(parts, state)
+            .await
+            .map_err(|_| ApiError::internal())?;
+
+        let key = bearer_token(parts)
+            .ok_or(ApiError::unauthorized("Missing server api key"))
+            .and_then(|token| {
+                ServerKey::from_str(token)
+                    .map_err(|_| ApiError::unauthorized("Invalid server api key"))
+            })?;
+
+        let server: Option<models::data::servers::Server> =
+            query_as("SELECT * FROM servers WHERE id = $1;")
+                .bind(key.id)
+                .fetch_optional(&pool)
+                .await?;
+
+        match server {
+            Some(server) if server.verify_api_key(&key) => Ok(Self(server)),
+            _ => Err(ApiError::unauthorized("Invalid server api key").into()),
+        }
+    }
+}
+
+pub fn bearer_token(parts: &Parts) -> Option<&str> {
+    parts
+        .headers
+        .get(AUTHORIZATION)?
+        .to_str()
+        .ok()?
+        .strip_prefix("Bearer ")
+        .map(str::trim)
+}
@@ -1,3 +1,12 @@
 pub mod auth;
+pub mod bans;
 pub mod servers;
 pub mod users;
+
+/// Escapes the wildcards of LIKE patterns so searches match them literally.
+pub fn escape_like(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_")
+}
@@ -1,55 +1,213 @@
-use anyhow::Result;
-use axum::{http::HeaderMap, Extension, Json};
-use josekit::jwt::{self};
-use sqlx::{query_as, PgPool};
-use std::str::FromStr;
+use super::bans::ensure_not_banned;
+use crate::{
+    error::AppError,
+    extractors::{AuthUser, Json, Path},
+    tokens::TokenKeys,
+};
+use axum::{
+    http::{
+        header::{CACHE_CONTROL, USER_AGENT},
+        HeaderMap, StatusCode,
+    },
+    response::IntoResponse,
+    Extension,
+};
+use models::{
+    api::{error::ApiError, users::Role},
+    data::sessions::RefreshToken,
+};
+use sqlx::{query, query_as, query_scalar, PgPool};
+use std::{str::FromStr, sync::Arc};
+use time::OffsetDateTime;
 use uuid::Uuid;
 
 pub async fn authenticate(
     Extension(pool): Extension<PgPool>,
+    Extension(keys): Extension<Arc<TokenKeys>>,
+    headers: HeaderMap,
     Json(credentials): Json<models::api::auth::Credentials>,
-) -> Result<Json<models::api::auth::AuthResponse>, ()> {
-    let user: models::data::users::User = query_as("SELECT * FROM users WHERE username = $1;")
-        .bind(credentials.username)
-        .fetch_one(&pool)
-        .await
-        .unwrap();
+) -> Result<Json<models::api::auth::AuthResponse>, AppError> {
+    let user: Option<models::data::users::User> =
+        query_as("SELECT * FROM users WHERE username = $1;")
+            .bind(credentials.username)
+            .fetch_optional(&pool)
+            .await?;
 
-    if user.verify_password(&credentials.password) {
-        return Ok(Json(models::api::auth::AuthResponse::from_user(user)));
-    }
+    match user {
+        Some(user) if user.verify_password(&credentials.password) => {
+            ensure_not_banned(&pool, user.id).await?;
 
-    Err(())
+            let response =
+                start_session(&pool, &keys, user.id, user.role, user_agent(&headers)).await?;
+
+            Ok(Json(response))
+        }
+        _ => Err(ApiError::unauthorized("Invalid username or password").into()),
+    }
 }
 
 pub async fn profile(
     Extension(pool): Extension<PgPool>,
-    headers: HeaderMap,
-) -> Json<models::api::users::User> {
-    let id = get_user_id_from_auth(headers).unwrap();
-
+    auth: AuthUser,
+) -> Result<Json<models::api::users::User>, AppError> {
     let user: models::data::users::User = query_as("SELECT * FROM users WHERE id = $1;")
-        .bind(id)
+        .bind(auth.user_id)
         .fetch_one(&pool)
-        .await
-        .unwrap();
-
-    Json(user.into())
-}
-
-pub fn get_user_id_from_auth(headers: HeaderMap) -> Result<Uuid, ()> {
-    if let Some(value) = headers.get("Authorization") {
-        if let Ok(value) = value.to_str() {
-            let (_key, token) = value.split_once("Bearer: ").unwrap();
-            if let Ok((payload, _header)) = jwt::decode_unsecured(token) {
-                if let Some(subject) = payload.subject() {
-                    if let Ok(id) = FromStr::from_str(subject) {
-                        return Ok(id);
-                    }
-                }
-            }
-        }
+        .await?;
+
+    Ok(Json(user.into()))
+}
+
+pub async fn refresh(
+    Extension(pool): Extension<PgPool>,
+    Extension(keys): Extension<Arc<TokenKeys>>,
+    Json(payload): Json<models::api::auth::RefreshRequest>,
+) -> Result<Json<models::api::auth::AuthResponse>, AppError> {
+    let token = RefreshToken::from_str(&payload.refresh_token)
+        .map_err(|_| ApiError::unauthorized("Invalid refresh token"))?;
+
+    let session: Option<models::data::sessions::Session> = query_as(
+        "SELECT * FROM sessions WHERE id = $1 AND revoked_at IS NULL AND expires_at > now();",
+    )
+    .bind(token.id)
+    .fetch_optional(&pool)
+    .await?;
+
+    let session = match session {
+        Some(session) if session.verify_refresh_token(&token) => session,
+        _ => return Err(ApiError::unauthorized("Invalid refresh token").into()),
+    };
+
+    ensure_not_banned(&pool, session.user_id).await?;
+
+    // Refresh tokens are single use, every refresh hands out a new one.
+    let refresh_token = RefreshToken::generate(session.id);
+    let now = OffsetDateTime::now_utc();
+
+    // Only the token that was checked may be swapped, so two refreshes racing with the same
+    // token can't both succeed.
+    let rotated = query("UPDATE sessions SET refresh_token_hash = $1, last_used_at = $2, expires_at = $3 WHERE id = $4 AND refresh_token_hash = $5;")
+        .bind(refresh_token.digest())
+        .bind(now)
+        .bind(now + keys.refresh_token_ttl)
+        .bind(session.id)
+        .bind(&session.refresh_token_hash)
+        .execute(&pool)
+        .await?;
+
+    if rotated.rows_affected() == 0 {
+        return Err(ApiError::unauthorized("Invalid refresh token").into());
     }
 
-    Err(())
+    let role: Role = query_scalar("SELECT role FROM users WHERE id = $1;")
+        .bind(session.user_id)
+        .fetch_one(&pool)
+        .await?;
+
+    let token = keys.access_token(session.user_id, session.id, role)?;
+
+    Ok(Json(models::api::auth::AuthResponse {
+        token,
+        expires_at: now + keys.access_token_ttl,
+        refresh_token: refresh_token.to_string(),
+    }))
+}
+
+pub async fn logout(
+    Extension(pool): Extension<PgPool>,
+    auth: AuthUser,
+) -> Result<StatusCode, AppError> {
+    revoke(&pool, auth.user_id, auth.session_id).await
+}
+
+pub async fn list_sessions(
+    Extension(pool): Extension<PgPool>,
+    auth: AuthUser,
+) -> Result<Json<Vec<models::api::auth::Session>>, AppError> {
+    let sessions: Vec<models::data::sessions::Session> = query_as(
+        "SELECT * FROM sessions WHERE user_id = $1 AND revoked_at IS NULL AND expires_at > now() ORDER BY last_used_at DESC;",
+    )
+    .bind(auth.user_id)
+    .fetch_all(&pool)
+    .await?;
+
+    let sessions = sessions
+        .into_iter()
+        .map(|session| models::api::auth::Session::from_data(session, auth.session_id))
+        .collect();
+
+    Ok(Json(sessions))
+}
+
+pub async fn revoke_session(
+    Extension(pool): Extension<PgPool>,
+    auth: AuthUser,
+    Path(id): Path<Uuid>,
+) -> Result<StatusCode, AppError> {
+    revoke(&pool, auth.user_id, id).await
+}
+
+/// Publishes the public keys tokens and join tickets can be verified with.
+pub async fn jwks(Extension(keys): Extension<Arc<TokenKeys>>) -> impl IntoResponse {
+    let jwks: serde_json::Map<String, serde_json::Value> = keys.jwks().into();
+
+    (
+        [(CACHE_CONTROL, "public, max-age=300")],
+        Json(serde_json::Value::Object(jwks)),
+    )
+}
+
+/// Creates a new session for the user and hands out its first token pair.
+pub async fn start_session(
+    pool: &PgPool,
+    keys: &TokenKeys,
+    user_id: Uuid,
+    role: Role,
+    user_agent: Option<String>,
+) -> Result<models::api::auth::AuthResponse, AppError> {
+    let session_id = Uuid::new_v4();
+    let refresh_token = RefreshToken::generate(session_id);
+    let now = OffsetDateTime::now_utc();
+
+    query("INSERT INTO sessions (id, user_id, refresh_token_hash, user_agent, expires_at) VALUES ($1, $2, $3, $4, $5);")
+        .bind(session_id)
+        .bind(user_id)
+        .bind(refresh_token.digest())
+        .bind(user_agent)
+        .bind(now + keys.refresh_token_ttl)
+        .execute(pool)
+        .await?;
+
+    let token = keys.access_token(user_id, session_id, role)?;
+
+    Ok(models::api::auth::AuthResponse {
+        token,
+        expires_at: now + keys.access_token_ttl,
+        refresh_token: refresh_token.to_string(),
+    })
+}
+
+/// The length of the user agent column of sessions, longer user agents are cut off.
+const MAX_USER_AGENT_LENGTH: usize = 255;
+
+pub fn user_agent(headers: &HeaderMap) -> Option<String> {
+    headers
+        .get(USER_AGENT)
+        .and_then(|value| value.to_str().ok())
+        .map(|value| value.chars().take(MAX_USER_AGENT_LENGTH).collect())
+}
+
+async fn revoke(pool: &PgPool, user_id: Uuid, session_id: Uuid) -> Result<StatusCode, AppError> {
+    let result = query(
+        "UPDATE sessions SET revoked_at = now() WHERE id = $1 AND user_id = $2 AND revoked_at IS NULL;",
+    )
+    .bind(session_id)
+    .bind(user_id)
+    .execute(pool)
+    .await?;
+
+    match result.rows_affected() {
+        0 => Err(ApiError::not_found("Session not found").into()),
+        _ => Ok(StatusCode::NO_CONTENT),
+    }
 }
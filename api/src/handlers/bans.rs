@@ -0,0 +1,144 @@
+use super::servers::ensure_key_owner;
+use crate::{
+    error::AppError,
+    extractors::{Admin, AuthServer, Json, Moderator, Path, RequireRole},
+};
+use axum::{http::StatusCode, Extension};
+use models::api::{
+    error::ApiError,
+    user_bans::{BanCheck, NewUserBan},
+    users::Role,
+};
+use sqlx::{query, query_as, query_scalar, PgPool};
+use time::OffsetDateTime;
+use uuid::Uuid;
+
+const ACTIVE: &str = "lifted_at IS NULL AND (expires_at IS NULL OR expires_at > now())";
+
+#[axum::debug_handler]
+pub async fn ban_user(
+    Extension(pool): Extension<PgPool>,
+    admin: RequireRole<Admin>,
+    Path(user_id): Path<Uuid>,
+    Json(payload): Json<NewUserBan>,
+) -> Result<Json<models::api::user_bans::UserBan>, AppError> {
+    if payload
+        .expires_at
+        .is_some_and(|expires_at| expires_at <= OffsetDateTime::now_utc())
+    {
+        return Err(ApiError::bad_request("A ban has to expire in the future").into());
+    }
+
+    let role: Option<Role> = query_scalar("SELECT role FROM users WHERE id = $1;")
+        .bind(user_id)
+        .fetch_optional(&pool)
+        .await?;
+
+    // Covers banning yourself too.
+    match role {
+        None => return Err(ApiError::not_found("User not found").into()),
+        Some(role) if role >= admin.user.role => {
+            return Err(
+                ApiError::forbidden("You can not ban users with your role or above").into(),
+            );
+        }
+        Some(_) => {}
+    }
+
+    let ban: models::data::user_bans::UserBan = query_as(
+        "INSERT INTO user_bans (user_id, reason, issued_by, expires_at) VALUES ($1, $2, $3, $4) RETURNING *;",
+    )
+    .bind(user_id)
+    .bind(payload.reason)
+    .bind(admin.user.user_id)
+    .bind(payload.expires_at)
+    .fetch_one(&pool)
+    .await
+    .map_err(|err| match err {
+        sqlx::Error::Database(err) if err.is_foreign_key_violation() => {
+            ApiError::not_found("User not found").into()
+        }
+        err => AppError::from(err),
+    })?;
+
+    // Banned users don't get to keep their sessions.
+    query("UPDATE sessions SET revoked_at = now() WHERE user_id = $1 AND revoked_at IS NULL;")
+        .bind(user_id)
+        .execute(&pool)
+        .await?;
+
+    Ok(Json(ban.into()))
+}
+
+#[axum::debug_handler]
+pub async fn lift_ban(
+    Extension(pool): Extension<PgPool>,
+    admin: RequireRole<Admin>,
+    Path(user_id): Path<Uuid>,
+) -> Result<StatusCode, AppError> {
+    let result = query(&format!(
+        "UPDATE user_bans SET lifted_at = now(), lifted_by = $1 WHERE user_id = $2 AND {ACTIVE};"
+    ))
+    .bind(admin.user.user_id)
+    .bind(user_id)
+    .execute(&pool)
+    .await?;
+
+    match result.rows_affected() {
+        0 => Err(ApiError::not_found("User is not banned").into()),
+        _ => Ok(StatusCode::NO_CONTENT),
+    }
+}
+
+#[axum::debug_handler]
+pub async fn list_user_bans(
+    Extension(pool): Extension<PgPool>,
+    _moderator: RequireRole<Moderator>,
+    Path(user_id): Path<Uuid>,
+) -> Result<Json<Vec<models::api::user_bans::UserBan>>, AppError> {
+    let bans: Vec<models::data::user_bans::UserBan> =
+        query_as("SELECT * FROM user_bans WHERE user_id = $1 ORDER BY created_at DESC;")
+            .bind(user_id)
+            .fetch_all(&pool)
+            .await?;
+
+    Ok(Json(bans.into_iter().map(Into::into).collect()))
+}
+
+/// Lets a game server find out which of its players have been banned.
+#[axum::debug_handler]
+pub async fn check_bans(
+    Extension(pool): Extension<PgPool>,
+    AuthServer(server): AuthServer,
+    Path(id): Path<Uuid>,
+    Json(payload): Json<BanCheck>,
+) -> Result<Json<Vec<models::api::user_bans::UserBan>>, AppError> {
+    ensure_key_owner(&server, id)?;
+
+    let bans: Vec<models::data::user_bans::UserBan> = query_as(&format!(
+        "SELECT DISTINCT ON (user_id) * FROM user_bans WHERE user_id = ANY($1) AND {ACTIVE} ORDER BY user_id, created_at DESC;"
+    ))
+    .bind(payload.user_ids)
+    .fetch_all(&pool)
+    .await?;
+
+    Ok(Json(bans.into_iter().map(Into::into).collect()))
+}
+
+/// Refuses users with an active ban.
+pub async fn ensure_not_banned(pool: &PgPool, user_id: Uuid) -> Result<(), AppError> {
+    let ban: Option<models::data::user_bans::UserBan> = query_as(&format!(
+        "SELECT * FROM user_bans WHERE user_id = $1 AND {ACTIVE} ORDER BY created_at DESC LIMIT 1;"
+    ))
+    .bind(user_id)
+    .fetch_optional(pool)
+    .await?;
+
+    match ban {
+        Some(ban) => {
+            let ban: models::api::user_bans::UserBan = ban.into();
+            Err(ApiError::forbidden(ban.message()).into())
+        }
+        None => Ok(()),
+    }
+}
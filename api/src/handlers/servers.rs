@@ -1,61 +1,361 @@
-use axum::{
-    extract::{ConnectInfo, Path},
-    Extension, Json,
+use super::{bans::ensure_not_banned, escape_like};
+use crate::{
+    error::AppError,
+    extractors::{AuthServer, AuthUser, Json, Path, Query},
+    tokens::{TokenKeys, JOIN_TICKET_TTL},
 };
-use sqlx::{query_as, PgPool};
-use std::net::SocketAddr;
+use axum::{extract::ConnectInfo, http::StatusCode, Extension};
+use models::{
+    api::{
+        error::ApiError,
+        servers::{ServerPage, ServerQuery, ServerSort},
+    },
+    data::servers::ServerKey,
+};
+use sqlx::{query, query_as, PgPool, Postgres, QueryBuilder};
+use std::{
+    fmt::{self, Display, Formatter},
+    net::SocketAddr,
+    str::FromStr,
+    sync::Arc,
+};
+use time::OffsetDateTime;
 use uuid::Uuid;
 
 #[axum::debug_handler]
 pub async fn list_servers(
     Extension(pool): Extension<PgPool>,
-) -> Json<Vec<models::api::servers::Server>> {
-    let servers: Vec<models::data::servers::Server> =
-        query_as("SELECT * FROM servers WHERE last_ping >= NOW() - INTERVAL '1 minute' ORDER BY last_ping DESC;")
-            .fetch_all(&pool)
-            .await
-            .unwrap();
+    Query(query): Query<ServerQuery>,
+) -> Result<Json<ServerPage>, AppError> {
+    let limit = query.limit();
+    let cursor = query
+        .cursor
+        .as_deref()
+        .map(|cursor| Cursor::parse(query.sort, cursor))
+        .transpose()?;
+
+    let mut builder = QueryBuilder::<Postgres>::new(
+        "SELECT * FROM servers WHERE last_ping >= NOW() - INTERVAL '1 minute'",
+    );
+
+    if let Some(search) = &query.search {
+        builder
+            .push(" AND name ILIKE ")
+            .push_bind(format!("%{}%", escape_like(search)));
+    }
+
+    if let Some(tag) = &query.tag {
+        builder.push(" AND ").push_bind(tag).push(" = ANY(tags)");
+    }
+
+    if let Some(game_mode) = &query.game_mode {
+        builder.push(" AND game_mode = ").push_bind(game_mode);
+    }
+
+    if let Some(region) = &query.region {
+        builder.push(" AND region = ").push_bind(region);
+    }
+
+    if query.not_full {
+        builder.push(" AND player_count < max_players");
+    }
+
+    if query.has_players {
+        builder.push(" AND player_count > 0");
+    }
+
+    match cursor {
+        Some(Cursor::LastPing(last_ping, id)) => {
+            builder
+                .push(" AND (last_ping, id) < (")
+                .push_bind(last_ping)
+                .push(", ")
+                .push_bind(id)
+                .push(")");
+        }
+        Some(Cursor::Players(player_count, id)) => {
+            builder
+                .push(" AND (player_count, id) < (")
+                .push_bind(player_count)
+                .push(", ")
+                .push_bind(id)
+                .push(")");
+        }
+        Some(Cursor::Name(name, id)) => {
+            builder
+                .push(" AND (name, id) > (")
+                .push_bind(name)
+                .push(", ")
+                .push_bind(id)
+                .push(")");
+        }
+        None => {}
+    }
 
-    let servers = servers.into_iter().map(Into::into).collect();
+    builder.push(match query.sort {
+        ServerSort::LastPing => " ORDER BY last_ping DESC, id DESC",
+        ServerSort::Players => " ORDER BY player_count DESC, id DESC",
+        ServerSort::Name => " ORDER BY name ASC, id ASC",
+    });
 
-    Json(servers)
+    // One extra row tells whether there is a next page.
+    builder.push(" LIMIT ").push_bind(i64::from(limit) + 1);
+
+    let mut servers: Vec<models::data::servers::Server> =
+        builder.build_query_as().fetch_all(&pool).await?;
+
+    let next_cursor = match servers.len() > limit as usize {
+        true => {
+            servers.truncate(limit as usize);
+            servers
+                .last()
+                .map(|server| Cursor::after(query.sort, server).to_string())
+        }
+        false => None,
+    };
+
+    Ok(Json(ServerPage {
+        servers: servers.into_iter().map(Into::into).collect(),
+        next_cursor,
+    }))
 }
 
 #[axum::debug_handler]
 pub async fn register_server(
     Extension(pool): Extension<PgPool>,
-    // TODO: Verify addr
-    ConnectInfo(_addr): ConnectInfo<SocketAddr>,
+    auth: AuthUser,
     Json(payload): Json<models::api::servers::RegisterServer>,
-) -> Json<models::api::servers::Server> {
+) -> Result<Json<models::api::servers::RegisteredServer>, AppError> {
     let port: i32 = payload.port.into();
 
+    // A server is identified by its owner, addr and port, registering it again keeps its id
+    // but hands out a new api key.
+    let key = ServerKey::generate(Uuid::new_v4());
+
     let server: models::data::servers::Server = query_as(
-        "INSERT INTO servers (name, addr, port, last_ping) VALUES ($1, $2, $3, now()) RETURNING *;",
+        "INSERT INTO servers (id, name, addr, port, last_ping, owner_id, api_key_hash, allow_any_addr) VALUES ($1, $2, $3, $4, now(), $5, $6, $7) \
+        ON CONFLICT (owner_id, addr, port) DO UPDATE SET name = EXCLUDED.name, last_ping = EXCLUDED.last_ping, api_key_hash = EXCLUDED.api_key_hash, allow_any_addr = EXCLUDED.allow_any_addr \
+        RETURNING *;",
     )
+    .bind(key.id)
     .bind(payload.name)
     .bind(payload.addr)
     .bind(port)
+    .bind(auth.user_id)
+    .bind(key.digest())
+    .bind(payload.allow_any_addr)
     .fetch_one(&pool)
-    .await
-    .unwrap();
+    .await?;
+
+    // The id only sticks for new servers, the digest doesn't depend on it.
+    let key = ServerKey {
+        id: server.id,
+        secret: key.secret,
+    };
 
-    Json(server.into())
+    Ok(Json(models::api::servers::RegisteredServer {
+        server: server.into(),
+        api_key: key.to_string(),
+    }))
+}
+
+#[axum::debug_handler]
+pub async fn update_server(
+    Extension(pool): Extension<PgPool>,
+    AuthServer(server): AuthServer,
+    Path(id): Path<Uuid>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    Json(payload): Json<models::api::servers::UpdateServer>,
+) -> Result<Json<models::api::servers::Server>, AppError> {
+    ensure_key_owner(&server, id)?;
+
+    // The new address has to match the caller just like it would on the next ping.
+    let mut updated = server;
+    updated.addr = payload.addr;
+    ensure_addr(&updated, addr)?;
+
+    let port: i32 = payload.port.into();
+
+    let server: models::data::servers::Server = query_as(
+        "UPDATE servers SET name = $1, addr = $2, port = $3, last_ping = now() WHERE id = $4 RETURNING *;",
+    )
+    .bind(payload.name)
+    .bind(payload.addr)
+    .bind(port)
+    .bind(id)
+    .fetch_one(&pool)
+    .await?;
+
+    Ok(Json(server.into()))
 }
 
 #[axum::debug_handler]
 pub async fn ping_server(
     Extension(pool): Extension<PgPool>,
+    AuthServer(server): AuthServer,
+    Path(id): Path<Uuid>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    Json(status): Json<models::api::servers::ServerStatus>,
+) -> Result<Json<models::api::servers::Server>, AppError> {
+    ensure_key_owner(&server, id)?;
+    ensure_addr(&server, addr)?;
+    status.validate()?;
+
+    let server: models::data::servers::Server = query_as(
+        "UPDATE servers SET last_ping = now(), player_count = $1, max_players = $2, game_mode = $3, map = $4, version = $5, region = $6, tags = $7 \
+        WHERE id = $8 RETURNING *;",
+    )
+    .bind(status.player_count as i32)
+    .bind(status.max_players as i32)
+    .bind(status.game_mode)
+    .bind(status.map)
+    .bind(status.version)
+    .bind(status.region)
+    .bind(status.tags)
+    .bind(id)
+    .fetch_one(&pool)
+    .await?;
+
+    Ok(Json(server.into()))
+}
+
+#[axum::debug_handler]
+pub async fn deregister_server(
+    Extension(pool): Extension<PgPool>,
+    AuthServer(server): AuthServer,
+    Path(id): Path<Uuid>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+) -> Result<StatusCode, AppError> {
+    ensure_key_owner(&server, id)?;
+    ensure_addr(&server, addr)?;
+
+    query("DELETE FROM servers WHERE id = $1;")
+        .bind(id)
+        .execute(&pool)
+        .await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[axum::debug_handler]
+pub async fn rotate_server_key(
+    Extension(pool): Extension<PgPool>,
+    auth: AuthUser,
     Path(id): Path<Uuid>,
-    // TODO: Verify addr
-    ConnectInfo(_addr): ConnectInfo<SocketAddr>,
-) -> Json<models::api::servers::Server> {
-    let server: models::data::servers::Server =
-        query_as("UPDATE servers SET last_ping = now() WHERE id = $1 RETURNING *;")
-            .bind(id)
-            .fetch_one(&pool)
-            .await
-            .unwrap();
-
-    Json(server.into())
+) -> Result<Json<models::api::servers::RegisteredServer>, AppError> {
+    let key = ServerKey::generate(id);
+
+    let server: Option<models::data::servers::Server> = query_as(
+        "UPDATE servers SET api_key_hash = $1 WHERE id = $2 AND owner_id = $3 RETURNING *;",
+    )
+    .bind(key.digest())
+    .bind(id)
+    .bind(auth.user_id)
+    .fetch_optional(&pool)
+    .await?;
+
+    let server = server.ok_or(ApiError::not_found("Server not found"))?;
+
+    Ok(Json(models::api::servers::RegisteredServer {
+        server: server.into(),
+        api_key: key.to_string(),
+    }))
+}
+
+/// The sort key and id of the last server of a page, the next page starts after it.
+enum Cursor {
+    LastPing(OffsetDateTime, Uuid),
+    Players(i32, Uuid),
+    Name(String, Uuid),
+}
+
+impl Cursor {
+    fn after(sort: ServerSort, server: &models::data::servers::Server) -> Self {
+        match sort {
+            ServerSort::LastPing => Self::LastPing(server.last_ping, server.id),
+            ServerSort::Players => Self::Players(server.player_count, server.id),
+            ServerSort::Name => Self::Name(server.name.clone(), server.id),
+        }
+    }
+
+    fn parse(sort: ServerSort, value: &str) -> Result<Self, ApiError> {
+        let invalid = || ApiError::bad_request("Invalid cursor");
+
+        let (key, id) = value.rsplit_once('.').ok_or_else(invalid)?;
+        let id = Uuid::from_str(id).map_err(|_| invalid())?;
+
+        Ok(match sort {
+            ServerSort::LastPing => {
+                let nanos = key.parse().map_err(|_| invalid())?;
+                let last_ping =
+                    OffsetDateTime::from_unix_timestamp_nanos(nanos).map_err(|_| invalid())?;
+                Self::LastPing(last_ping, id)
+            }
+            ServerSort::Players => Self::Players(key.parse().map_err(|_| invalid())?, id),
+            ServerSort::Name => Self::Name(key.to_string(), id),
+        })
+    }
+}
+
+impl Display for Cursor {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Cursor::LastPing(last_ping, id) => {
+                write!(f, "{}.{id}", last_ping.unix_timestamp_nanos())
+            }
+            Cursor::Players(player_count, id) => write!(f, "{player_count}.{id}"),
+            Cursor::Name(name, id) => write!(f, "{name}.{id}"),
+        }
+    }
+}
+
+pub fn ensure_key_owner(server: &models::data::servers::Server, id: Uuid) -> Result<(), ApiError> {
+    if server.id != id {
+        return Err(ApiError::forbidden("Api key belongs to another server"));
+    }
+
+    Ok(())
+}
+
+fn ensure_addr(server: &models::data::servers::Server, addr: SocketAddr) -> Result<(), ApiError> {
+    if !server.accepts_addr(addr.ip()) {
+        return Err(ApiError::forbidden(format!(
+            "Request from {} does not match the server address {}",
+            addr.ip(),
+            server.addr
+        )));
+    }
+
+    Ok(())
+}
+
+#[axum::debug_handler]
+pub async fn issue_join_ticket(
+    Extension(pool): Extension<PgPool>,
+    Extension(keys): Extension<Arc<TokenKeys>>,
+    auth: AuthUser,
+    Path(id): Path<Uuid>,
+) -> Result<Json<models::api::servers::JoinTicket>, AppError> {
+    let server: Option<models::data::servers::Server> = query_as(
+        "SELECT * FROM servers WHERE id = $1 AND last_ping >= NOW() - INTERVAL '1 minute';",
+    )
+    .bind(id)
+    .fetch_optional(&pool)
+    .await?;
+
+    let server = server.ok_or(ApiError::not_found("Server not found"))?;
+
+    ensure_not_banned(&pool, auth.user_id).await?;
+
+    let user: models::data::users::User = query_as("SELECT * FROM users WHERE id = $1;")
+        .bind(auth.user_id)
+        .fetch_one(&pool)
+        .await?;
+
+    let ticket = keys.join_ticket(user.id, &user.username, user.role, server.id)?;
+
+    Ok(Json(models::api::servers::JoinTicket {
+        ticket,
+        expires_at: OffsetDateTime::now_utc() + JOIN_TICKET_TTL,
+    }))
 }
@@ -1,11 +1,29 @@
-use axum::{extract::Path, Extension, Json};
-use sqlx::{query_as, PgPool};
+use super::{
+    auth::{start_session, user_agent},
+    escape_like,
+};
+use crate::{
+    error::AppError,
+    extractors::{Admin, Json, Path, Query, RequireRole},
+    tokens::TokenKeys,
+};
+use axum::{http::HeaderMap, Extension};
+use models::api::{
+    error::{ApiError, FieldError},
+    users::{SetRole, UserDetails, UserQuery},
+};
+use sqlx::{query_as, PgPool, Postgres, QueryBuilder};
+use std::sync::Arc;
 use uuid::Uuid;
 
 pub async fn register_user(
     Extension(pool): Extension<PgPool>,
+    Extension(keys): Extension<Arc<TokenKeys>>,
+    headers: HeaderMap,
     Json(payload): Json<models::api::users::NewUser>,
-) -> Json<models::api::auth::AuthResponse> {
+) -> Result<Json<models::api::auth::AuthResponse>, AppError> {
+    payload.validate()?;
+
     let new_user: models::data::users::NewUser = payload.into();
 
     let user: models::data::users::User = query_as(
@@ -16,20 +34,85 @@ pub async fn register_user(
     .bind(new_user.password_hash)
     .fetch_one(&pool)
     .await
-    .unwrap();
+    .map_err(|err| match err {
+        sqlx::Error::Database(err) if err.is_unique_violation() => {
+            let mut error = ApiError::conflict("Email already registered");
+            error.fields = vec![FieldError::new("email", "Email already registered")];
+            error.into()
+        }
+        err => AppError::from(err),
+    })?;
+
+    let response = start_session(&pool, &keys, user.id, user.role, user_agent(&headers)).await?;
 
-    Json(models::api::auth::AuthResponse::from_user(user))
+    Ok(Json(response))
 }
 
 pub async fn get_user(
     Extension(pool): Extension<PgPool>,
     Path(id): Path<Uuid>,
-) -> Json<models::api::users::User> {
-    let user: models::data::users::User = query_as("SELECT * FROM users WHERE id = $1;")
+) -> Result<Json<models::api::users::User>, AppError> {
+    let user: Option<models::data::users::User> = query_as("SELECT * FROM users WHERE id = $1;")
         .bind(id)
-        .fetch_one(&pool)
-        .await
-        .unwrap();
+        .fetch_optional(&pool)
+        .await?;
+
+    let user = user.ok_or(ApiError::not_found("User not found"))?;
+
+    Ok(Json(user.into()))
+}
+
+pub async fn list_users(
+    Extension(pool): Extension<PgPool>,
+    _admin: RequireRole<Admin>,
+    Query(query): Query<UserQuery>,
+) -> Result<Json<Vec<UserDetails>>, AppError> {
+    let mut builder = QueryBuilder::<Postgres>::new("SELECT * FROM users WHERE true");
+
+    if let Some(search) = &query.search {
+        let pattern = format!("%{}%", escape_like(search));
+
+        builder
+            .push(" AND (username ILIKE ")
+            .push_bind(pattern.clone())
+            .push(" OR email ILIKE ")
+            .push_bind(pattern)
+            .push(")");
+    }
+
+    if let Some(role) = query.role {
+        builder.push(" AND role = ").push_bind(role);
+    }
+
+    builder
+        .push(" ORDER BY username, id LIMIT ")
+        .push_bind(i64::from(query.limit.unwrap_or(50).clamp(1, 100)))
+        .push(" OFFSET ")
+        .push_bind(i64::from(query.offset.unwrap_or(0)));
+
+    let users: Vec<models::data::users::User> = builder.build_query_as().fetch_all(&pool).await?;
+
+    Ok(Json(users.into_iter().map(Into::into).collect()))
+}
+
+pub async fn set_user_role(
+    Extension(pool): Extension<PgPool>,
+    admin: RequireRole<Admin>,
+    Path(id): Path<Uuid>,
+    Json(payload): Json<SetRole>,
+) -> Result<Json<UserDetails>, AppError> {
+    if id == admin.user.user_id {
+        return Err(ApiError::forbidden("You can not change your own role").into());
+    }
+
+    let user: Option<models::data::users::User> =
+        query_as("UPDATE users SET role = $1 WHERE id = $2 RETURNING *;")
+            .bind(payload.role)
+            .bind(id)
+            .fetch_optional(&pool)
+            .await?;
+
+    let user = user.ok_or(ApiError::not_found("User not found"))?;
 
-    Json(user.into())
+    Ok(Json(user.into()))
 }
@@ -0,0 +1,27 @@
+use sqlx::{query, PgPool};
+use std::time::Duration;
+use time::OffsetDateTime;
+use tokio::time::interval;
+
+/// Periodically removes servers that haven't pinged within `retention`.
+pub async fn prune_servers(pool: PgPool, retention: Duration, every: Duration) {
+    let mut ticker = interval(every);
+
+    loop {
+        ticker.tick().await;
+
+        let cutoff = OffsetDateTime::now_utc() - retention;
+
+        match query("DELETE FROM servers WHERE last_ping < $1;")
+            .bind(cutoff)
+            .execute(&pool)
+            .await
+        {
+            Ok(result) if result.rows_affected() > 0 => {
+                tracing::info!("Pruned {} stale servers", result.rows_affected());
+            }
+            Ok(_) => {}
+            Err(err) => tracing::error!("Failed to prune servers: {err}"),
+        }
+    }
+}
@@ -1,19 +1,29 @@
 use axum::{
-    routing::{get, post},
+    routing::{delete, get, post, put},
     Extension, Router,
 };
 use clap::Parser;
 use dotenvy::dotenv;
 use handlers::{
-    auth::{authenticate, profile},
-    servers::{list_servers, ping_server, register_server},
-    users::{get_user, register_user},
+    auth::{authenticate, jwks, list_sessions, logout, profile, refresh, revoke_session},
+    bans::{ban_user, check_bans, lift_ban, list_user_bans},
+    servers::{
+        deregister_server, issue_join_ticket, list_servers, ping_server, register_server,
+        rotate_server_key, update_server,
+    },
+    users::{get_user, list_users, register_user, set_user_role},
 };
 use sqlx::postgres::PgPoolOptions;
-use std::{env, net::SocketAddr};
+use std::{env, net::SocketAddr, sync::Arc, time::Duration};
+use tokens::TokenKeys;
 use tower_http::trace::TraceLayer;
+use uuid::Uuid;
 
+mod error;
+mod extractors;
 mod handlers;
+mod jobs;
+mod tokens;
 
 #[derive(Parser, Debug)]
 #[command(version, about, long_about = None)]
@@ -21,6 +31,26 @@ struct ApiArgs {
     /// The port to run the server on
     #[arg(short, long, default_value = "3000")]
     port: u16,
+
+    /// How long issued access tokens stay valid, in seconds
+    #[arg(long, default_value = "900")]
+    access_token_ttl: u64,
+
+    /// How long a session can go unused before its refresh token expires, in seconds
+    #[arg(long, default_value = "2592000")]
+    refresh_token_ttl: u64,
+
+    /// How long a server can go without pinging before its registration is removed, in seconds
+    #[arg(long, default_value = "3600")]
+    server_retention: u64,
+
+    /// How often stale servers are pruned, in seconds
+    #[arg(long, default_value = "60")]
+    prune_interval: u64,
+
+    /// Comma separated ids of users promoted to admin on startup, to bootstrap the first admins
+    #[arg(long, env = "ADMIN_USER_IDS", value_delimiter = ',')]
+    admin_user_ids: Vec<Uuid>,
 }
 
 #[tokio::main]
@@ -41,13 +71,49 @@ async fn main() {
 
     let args = ApiArgs::parse();
 
+    if !args.admin_user_ids.is_empty() {
+        sqlx::query("UPDATE users SET role = 'admin' WHERE id = ANY($1);")
+            .bind(&args.admin_user_ids)
+            .execute(&pool)
+            .await
+            .expect("could not promote the admin users");
+    }
+
+    let keys = TokenKeys::from_env(
+        Duration::from_secs(args.access_token_ttl),
+        Duration::from_secs(args.refresh_token_ttl),
+    )
+    .expect("could not load the token signing key");
+
+    tokio::spawn(jobs::prune_servers(
+        pool.clone(),
+        Duration::from_secs(args.server_retention),
+        Duration::from_secs(args.prune_interval),
+    ));
+
     let app = Router::new()
+        .route("/.well-known/jwks.json", get(jwks))
         .route("/auth", get(profile).post(authenticate))
+        .route("/auth/refresh", post(refresh))
+        .route("/auth/logout", post(logout))
+        .route("/auth/sessions", get(list_sessions))
+        .route("/auth/sessions/:id", delete(revoke_session))
         .route("/servers", get(list_servers).post(register_server))
+        .route("/servers/:id", put(update_server).delete(deregister_server))
+        .route("/servers/:id/key", post(rotate_server_key))
         .route("/servers/:id/ping", post(ping_server))
+        .route("/servers/:id/tickets", post(issue_join_ticket))
+        .route("/admin/users", get(list_users))
+        .route("/admin/users/:id/role", put(set_user_role))
         .route("/users", post(register_user))
         .route("/users/:id", get(get_user))
+        .route(
+            "/users/:id/bans",
+            get(list_user_bans).post(ban_user).delete(lift_ban),
+        )
+        .route("/servers/:id/bans/check", post(check_bans))
         .layer(Extension(pool))
+        .layer(Extension(Arc::new(keys)))
         .layer(TraceLayer::new_for_http());
 
     let listener = tokio::net::TcpListener::bind(format!("0.0.0.0:{}", args.port))
@@ -0,0 +1,264 @@
+use anyhow::{anyhow, Result};
+use josekit::{
+    jwk::{Jwk, JwkSet},
+    jws::{JwsHeader, JwsSigner, JwsVerifier, ES256, HS256, RS256},
+    jwt::{self, JwtPayload, JwtPayloadValidator},
+    Value,
+};
+use models::api::users::Role;
+use std::{
+    env, fs,
+    path::Path,
+    str::FromStr,
+    time::{Duration, SystemTime},
+};
+use uuid::Uuid;
+
+pub const JOIN_TICKET_TTL: Duration = Duration::from_secs(30);
+
+struct SigningKey {
+    kid: String,
+    signer: Box<dyn JwsSigner>,
+    verifier: Box<dyn JwsVerifier>,
+    /// Only asymmetric keys have a public key that can be published.
+    public_key: Option<Jwk>,
+}
+
+impl SigningKey {
+    fn hmac(kid: String, secret: &[u8]) -> Result<Self> {
+        Ok(Self {
+            kid,
+            signer: Box::new(HS256.signer_from_bytes(secret)?),
+            verifier: Box::new(HS256.verifier_from_bytes(secret)?),
+            public_key: None,
+        })
+    }
+
+    /// Loads a PEM encoded ES256 or RS256 private key, named after its file stem.
+    fn from_pem_file(path: &Path) -> Result<Self> {
+        let kid = path
+            .file_stem()
+            .and_then(|stem| stem.to_str())
+            .ok_or(anyhow!("invalid key file name {}", path.display()))?
+            .to_string();
+        let pem = fs::read(path)?;
+
+        let (signer, mut public_key, algorithm): (Box<dyn JwsSigner>, Jwk, &str) =
+            if let Ok(key_pair) = ES256.key_pair_from_pem(&pem) {
+                (
+                    Box::new(ES256.signer_from_pem(&pem)?),
+                    key_pair.to_jwk_public_key(),
+                    "ES256",
+                )
+            } else {
+                let key_pair = RS256.key_pair_from_pem(&pem)?;
+                (
+                    Box::new(RS256.signer_from_pem(&pem)?),
+                    key_pair.to_jwk_public_key(),
+                    "RS256",
+                )
+            };
+
+        public_key.set_key_id(&kid);
+        public_key.set_algorithm(algorithm);
+        public_key.set_key_use("sig");
+
+        let verifier: Box<dyn JwsVerifier> = match algorithm {
+            "ES256" => Box::new(ES256.verifier_from_jwk(&public_key)?),
+            _ => Box::new(RS256.verifier_from_jwk(&public_key)?),
+        };
+
+        Ok(Self {
+            kid,
+            signer,
+            verifier,
+            public_key: Some(public_key),
+        })
+    }
+}
+
+/// Signs and verifies the tokens handed out by the api.
+///
+/// Keys are configured through the environment:
+/// - `JWT_SECRET` adds an HS256 key with the id `hmac`
+/// - `JWT_PRIVATE_KEY` adds a PEM encoded ES256 or RS256 private key
+/// - `JWT_KEYS_DIR` adds every `<kid>.pem` private key in the directory
+///
+/// Tokens are signed with the key named by `JWT_ACTIVE_KID`, or the last asymmetric key
+/// in file name order. The other keys are only used for verification, which allows keys to be
+/// rotated by adding a new one and removing the old one once its tokens have expired.
+pub struct TokenKeys {
+    issuer: String,
+    pub access_token_ttl: Duration,
+    pub refresh_token_ttl: Duration,
+    keys: Vec<SigningKey>,
+    active: usize,
+}
+
+impl TokenKeys {
+    pub fn from_env(access_token_ttl: Duration, refresh_token_ttl: Duration) -> Result<Self> {
+        let issuer = env::var("JWT_ISSUER").unwrap_or_else(|_| String::from("cq-api"));
+
+        let mut keys = Vec::new();
+
+        if let Ok(secret) = env::var("JWT_SECRET") {
+            keys.push(SigningKey::hmac(String::from("hmac"), secret.as_bytes())?);
+        }
+
+        if let Ok(path) = env::var("JWT_PRIVATE_KEY") {
+            keys.push(SigningKey::from_pem_file(Path::new(&path))?);
+        }
+
+        if let Ok(dir) = env::var("JWT_KEYS_DIR") {
+            let mut paths = fs::read_dir(dir)?
+                .map(|entry| entry.map(|entry| entry.path()))
+                .collect::<Result<Vec<_>, _>>()?;
+            paths.retain(|path| path.extension().is_some_and(|extension| extension == "pem"));
+            paths.sort();
+
+            for path in paths {
+                keys.push(SigningKey::from_pem_file(&path)?);
+            }
+        }
+
+        let active = match env::var("JWT_ACTIVE_KID") {
+            Ok(kid) => keys
+                .iter()
+                .position(|key| key.kid == kid)
+                .ok_or(anyhow!("no key found for JWT_ACTIVE_KID {kid}"))?,
+            Err(_) => keys
+                .iter()
+                .rposition(|key| key.public_key.is_some())
+                .or(keys.len().checked_sub(1))
+                .ok_or(anyhow!(
+                    "one of JWT_SECRET, JWT_PRIVATE_KEY or JWT_KEYS_DIR must be set"
+                ))?,
+        };
+
+        Ok(Self {
+            issuer,
+            access_token_ttl,
+            refresh_token_ttl,
+            keys,
+            active,
+        })
+    }
+
+    pub fn sign(&self, payload: JwtPayload, ttl: Duration) -> Result<String> {
+        self.sign_with(&self.keys[self.active], payload, ttl)
+    }
+
+    fn sign_with(
+        &self,
+        key: &SigningKey,
+        mut payload: JwtPayload,
+        ttl: Duration,
+    ) -> Result<String> {
+        let now = SystemTime::now();
+
+        payload.set_issuer(&self.issuer);
+        payload.set_issued_at(&now);
+        payload.set_expires_at(&(now + ttl));
+
+        let mut header = JwsHeader::new();
+        header.set_token_type("JWT");
+        header.set_key_id(&key.kid);
+
+        Ok(jwt::encode_with_signer(&payload, &header, &*key.signer)?)
+    }
+
+    /// Verifies the signature and the `iss`, `iat` and `exp` claims of a token.
+    ///
+    /// Tokens without a `kid` header are checked against the active key.
+    pub fn verify(&self, token: &str) -> Result<JwtPayload> {
+        let (payload, _header) = jwt::decode_with_verifier_selector(token, |header| {
+            let key = match header.key_id() {
+                Some(kid) => self.keys.iter().find(|key| key.kid == kid),
+                None => self.keys.get(self.active),
+            };
+
+            Ok(key.map(|key| &*key.verifier))
+        })?;
+
+        if payload.expires_at().is_none() || payload.issued_at().is_none() {
+            return Err(anyhow!("token is missing exp or iat"));
+        }
+
+        let mut validator = JwtPayloadValidator::new();
+        validator.set_issuer(&self.issuer);
+        validator.validate(&payload)?;
+
+        Ok(payload)
+    }
+
+    /// The public keys other services can verify tokens with.
+    pub fn jwks(&self) -> JwkSet {
+        let mut jwks = JwkSet::new();
+
+        for key in self.keys.iter() {
+            if let Some(public_key) = &key.public_key {
+                jwks.push_key(public_key.clone());
+            }
+        }
+
+        jwks
+    }
+
+    pub fn access_token(&self, user_id: Uuid, session_id: Uuid, role: Role) -> Result<String> {
+        let mut payload = JwtPayload::new();
+        payload.set_subject(user_id.to_string());
+        payload.set_claim("sid", Some(Value::String(session_id.to_string())))?;
+        payload.set_claim("role", Some(Value::String(role.as_str().to_string())))?;
+
+        self.sign(payload, self.access_token_ttl)
+    }
+
+    pub fn verify_access_token(&self, token: &str) -> Result<AccessClaims> {
+        let payload = self.verify(token)?;
+        let subject = payload.subject().ok_or(anyhow!("token has no subject"))?;
+        let session_id = payload
+            .claim("sid")
+            .and_then(Value::as_str)
+            .ok_or(anyhow!("token has no session"))?;
+
+        Ok(AccessClaims {
+            user_id: Uuid::from_str(subject)?,
+            session_id: Uuid::from_str(session_id)?,
+        })
+    }
+
+    /// Issues a ticket allowing the user to join a single game server.
+    ///
+    /// Game servers verify these offline against the published keys, so tickets are signed
+    /// with the active key when it is asymmetric, and otherwise the last asymmetric key.
+    pub fn join_ticket(
+        &self,
+        user_id: Uuid,
+        username: &str,
+        role: Role,
+        server_id: Uuid,
+    ) -> Result<String> {
+        let key = match self.keys[self.active].public_key {
+            Some(_) => &self.keys[self.active],
+            None => self
+                .keys
+                .iter()
+                .rfind(|key| key.public_key.is_some())
+                .ok_or(anyhow!("join tickets require an asymmetric signing key"))?,
+        };
+
+        let mut payload = JwtPayload::new();
+        payload.set_subject(user_id.to_string());
+        payload.set_audience(vec![server_id.to_string()]);
+        payload.set_claim("typ", Some(Value::String(String::from("join"))))?;
+        payload.set_claim("name", Some(Value::String(username.to_string())))?;
+        payload.set_claim("role", Some(Value::String(role.as_str().to_string())))?;
+
+        self.sign_with(key, payload, JOIN_TICKET_TTL)
+    }
+}
+
+pub struct AccessClaims {
+    pub user_id: Uuid,
+    pub session_id: Uuid,
+}
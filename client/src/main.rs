@@ -5,10 +5,14 @@ use engine::{models::network::ClientMessage, plugins::movement::MovementPlugin};
 use plugins::{
     api::{ApiPlugin, ApiResource},
     controller::ControllerPlugin,
+    interpolation::InterpolationPlugin,
     network::NetworkPlugin,
+    prediction::PredictionPlugin,
     render::RenderPlugin,
-    ui::UiPlugin,
+    ui::{LocalOffset, UiPlugin},
 };
+use std::time::Duration;
+use time::UtcOffset;
 use uuid::Uuid;
 
 mod components;
@@ -19,6 +23,10 @@ mod plugins;
 struct ClientArgs {
     #[arg(short, long, default_value = "http://localhost:3000")]
     api_base_url: String,
+
+    /// How far in the past other players are shown, in milliseconds, higher hides more jitter
+    #[arg(long, default_value = "100")]
+    interpolation_delay: u64,
 }
 
 #[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Default, States)]
@@ -44,10 +52,14 @@ enum ClientEvent {
 fn main() {
     let args = ClientArgs::parse();
 
+    // Only available while the process has a single thread, so before bevy starts its own.
+    let local_offset = UtcOffset::current_local_offset().unwrap_or(UtcOffset::UTC);
+
     App::new()
         .add_plugins(DefaultPlugins)
         .add_plugins(ApiPlugin::new(args.api_base_url.clone()))
         .add_plugins(UiPlugin)
+        .insert_resource(LocalOffset(local_offset))
         .add_plugins(NetworkPlugin)
         .init_state::<AuthState>()
         .init_state::<ConnectionState>()
@@ -56,6 +68,10 @@ fn main() {
         .add_plugins(RenderPlugin)
         .add_plugins(ControllerPlugin)
         .add_plugins(MovementPlugin)
+        .add_plugins(PredictionPlugin)
+        .add_plugins(InterpolationPlugin::new(Duration::from_millis(
+            args.interpolation_delay,
+        )))
         .run();
 }
 
@@ -66,10 +82,12 @@ fn connection_event_handler(
     client: Res<QuinnetClient>,
 ) {
     for _ in connection_event_reader.read() {
-        if let Some(user) = &api.profile.data {
+        if let Some(join_ticket) = &api.join_ticket.data {
             client
                 .connection()
-                .send_message(ClientMessage::Join { user_id: user.id })
+                .send_message(ClientMessage::Join {
+                    ticket: join_ticket.ticket.clone(),
+                })
                 .unwrap();
         }
         next_connection_state.set(ConnectionState::Connected);
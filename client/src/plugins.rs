@@ -1,5 +1,7 @@
 pub mod api;
 pub mod controller;
+pub mod interpolation;
 pub mod network;
+pub mod prediction;
 pub mod render;
 pub mod ui;
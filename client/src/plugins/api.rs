@@ -1,15 +1,26 @@
-use crate::AuthState;
+use crate::{AuthState, ClientEvent};
 use bevy::prelude::*;
-use engine::api_client::{authenticate, get_profile, get_user, list_servers, register_user};
+use engine::api_client::{
+    authenticate, get_profile, get_user, issue_join_ticket, list_servers, logout, refresh,
+    register_user,
+};
 use models::api::{
     auth::{AuthResponse, Credentials},
-    servers::Server,
+    error::{ApiError, ErrorCode},
+    servers::{JoinTicket, Server, ServerPage, ServerQuery},
     users::{NewUser, User},
 };
-use std::collections::HashMap;
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+use time::OffsetDateTime;
 use tokio::{runtime::Runtime, sync::mpsc};
 use uuid::Uuid;
 
+/// How long to wait before trying again when a refresh failed for any reason but a rejected token.
+const REFRESH_RETRY_DELAY: Duration = Duration::from_secs(5);
+
 pub struct ApiPlugin {
     base_url: String,
 }
@@ -25,7 +36,11 @@ impl Plugin for ApiPlugin {
         app.insert_resource(ApiResource::new(self.base_url.clone()))
             .add_event::<ApiEvent>()
             .add_systems(Update, api_event_handler_system)
-            .add_systems(Update, api_message_handler_system);
+            .add_systems(Update, api_message_handler_system)
+            .add_systems(
+                Update,
+                refresh_token_system.run_if(in_state(AuthState::Authenticated)),
+            );
     }
 }
 
@@ -40,17 +55,24 @@ pub enum ApiEvent {
         email: String,
         password: String,
     },
+    RefreshToken,
+    Logout,
     LoadProfile,
     LoadServers,
+    LoadMoreServers,
     LoadUser(Uuid),
+    RequestJoinTicket(Uuid),
 }
 
 enum ApiMessage {
-    AuthenticateFulfilled(Result<String, ()>),
-    RegisterFulfilled(Result<String, ()>),
-    LoadProfileFulfilled(Result<User, ()>),
-    LoadServersFulfilled(Result<Vec<Server>, ()>),
-    LoadUserFulfilled((Uuid, Result<User, ()>)),
+    AuthenticateFulfilled(Result<AuthResponse, ApiError>),
+    RegisterFulfilled(Result<AuthResponse, ApiError>),
+    RefreshTokenFulfilled(Result<AuthResponse, ApiError>),
+    LoadProfileFulfilled(Result<User, ApiError>),
+    /// Whether the page continues the loaded servers, and the page
+    LoadServersFulfilled((bool, Result<ServerPage, ApiError>)),
+    LoadUserFulfilled((Uuid, Result<User, ApiError>)),
+    JoinTicketFulfilled((Uuid, Result<JoinTicket, ApiError>)),
 }
 
 #[derive(Resource)]
@@ -59,10 +81,15 @@ pub struct ApiResource {
     runtime: Runtime,
     tx: mpsc::Sender<ApiMessage>,
     rx: mpsc::Receiver<ApiMessage>,
-    pub token: LoadableData<String>,
+    pub token: LoadableData<AuthResponse>,
+    /// When the token may be refreshed again after a failed attempt
+    refresh_retry_at: Option<Instant>,
     pub profile: LoadableData<User>,
     pub servers: LoadableData<Vec<Server>>,
+    pub server_query: ServerQuery,
+    pub next_servers_cursor: Option<String>,
     pub users: HashMap<Uuid, LoadableData<User>>,
+    pub join_ticket: LoadableData<JoinTicket>,
 }
 
 impl ApiResource {
@@ -80,9 +107,13 @@ impl ApiResource {
             tx,
             rx,
             token: LoadableData::default(),
+            refresh_retry_at: None,
             profile: LoadableData::default(),
             servers: LoadableData::default(),
+            server_query: ServerQuery::default(),
+            next_servers_cursor: None,
             users: HashMap::new(),
+            join_ticket: LoadableData::default(),
         }
     }
 }
@@ -91,6 +122,7 @@ impl ApiResource {
 pub struct LoadableData<T> {
     pub loading: bool,
     pub data: Option<T>,
+    pub error: Option<ApiError>,
 }
 
 impl<T> Default for LoadableData<T> {
@@ -98,6 +130,7 @@ impl<T> Default for LoadableData<T> {
         Self {
             loading: false,
             data: None,
+            error: None,
         }
     }
 }
@@ -105,6 +138,7 @@ impl<T> Default for LoadableData<T> {
 impl<T> LoadableData<T> {
     fn start(&mut self) {
         self.loading = true;
+        self.error = None;
     }
 
     fn finish(&mut self, data: T) {
@@ -112,13 +146,18 @@ impl<T> LoadableData<T> {
         self.loading = false;
     }
 
-    fn failed(&mut self) {
+    fn failed(&mut self, error: ApiError) {
         self.data = None;
         self.loading = false;
+        self.error = Some(error);
     }
 }
 
-fn api_event_handler_system(mut api: ResMut<ApiResource>, mut events: EventReader<ApiEvent>) {
+fn api_event_handler_system(
+    mut api: ResMut<ApiResource>,
+    mut events: EventReader<ApiEvent>,
+    mut auth_state: ResMut<NextState<AuthState>>,
+) {
     for event in events.read() {
         match event {
             ApiEvent::Authenticate { username, password } => {
@@ -135,8 +174,8 @@ fn api_event_handler_system(mut api: ResMut<ApiResource>, mut events: EventReade
                             match authenticate(&api_base_url, &Credentials { username, password })
                                 .await
                             {
-                                Ok(AuthResponse { token }) => Ok(token),
-                                Err(_) => Err(()),
+                                Ok(auth_response) => Ok(auth_response),
+                                Err(err) => Err(err.into()),
                             };
 
                         tx.send(ApiMessage::AuthenticateFulfilled(result))
@@ -170,8 +209,8 @@ fn api_event_handler_system(mut api: ResMut<ApiResource>, mut events: EventReade
                         )
                         .await
                         {
-                            Ok(AuthResponse { token }) => Ok(token),
-                            Err(_) => Err(()),
+                            Ok(auth_response) => Ok(auth_response),
+                            Err(err) => Err(err.into()),
                         };
 
                         tx.send(ApiMessage::RegisterFulfilled(result))
@@ -180,19 +219,56 @@ fn api_event_handler_system(mut api: ResMut<ApiResource>, mut events: EventReade
                     });
                 }
             }
+            ApiEvent::RefreshToken => {
+                if !api.token.loading {
+                    if let Some(auth_response) = &api.token.data {
+                        let tx = api.tx.clone();
+                        let api_base_url = api.base_url.clone();
+                        let refresh_token = auth_response.refresh_token.clone();
+
+                        api.token.start();
+
+                        api.runtime.spawn(async move {
+                            let result = match refresh(&api_base_url, &refresh_token).await {
+                                Ok(auth_response) => Ok(auth_response),
+                                Err(err) => Err(err.into()),
+                            };
+
+                            tx.send(ApiMessage::RefreshTokenFulfilled(result))
+                                .await
+                                .unwrap();
+                        });
+                    }
+                }
+            }
+            ApiEvent::Logout => {
+                if let Some(auth_response) = &api.token.data {
+                    let api_base_url = api.base_url.clone();
+                    let token = auth_response.token.clone();
+
+                    api.runtime.spawn(async move {
+                        // The session is dropped locally even if the api can't be reached.
+                        let _ = logout(&api_base_url, &token).await;
+                    });
+                }
+
+                api.token = LoadableData::default();
+                api.profile = LoadableData::default();
+                auth_state.set(AuthState::Anonymous);
+            }
             ApiEvent::LoadProfile => {
                 if !api.profile.loading {
                     api.profile.start();
 
                     let tx = api.tx.clone();
                     let api_base_url = api.base_url.clone();
-                    if let Some(token) = &api.token.data {
-                        let token = token.clone();
+                    if let Some(auth_response) = &api.token.data {
+                        let token = auth_response.token.clone();
 
                         api.runtime.spawn(async move {
                             let result = match get_profile(&api_base_url, &token).await {
                                 Ok(user) => Ok(user),
-                                Err(_) => Err(()),
+                                Err(err) => Err(err.into()),
                             };
 
                             tx.send(ApiMessage::LoadProfileFulfilled(result))
@@ -202,20 +278,29 @@ fn api_event_handler_system(mut api: ResMut<ApiResource>, mut events: EventReade
                     }
                 }
             }
-            ApiEvent::LoadServers => {
-                if !api.servers.loading {
+            ApiEvent::LoadServers | ApiEvent::LoadMoreServers => {
+                let append = matches!(event, ApiEvent::LoadMoreServers);
+
+                if !api.servers.loading && (!append || api.next_servers_cursor.is_some()) {
                     api.servers.start();
 
                     let tx = api.tx.clone();
                     let api_base_url = api.base_url.clone();
+                    let query = ServerQuery {
+                        cursor: match append {
+                            true => api.next_servers_cursor.clone(),
+                            false => None,
+                        },
+                        ..api.server_query.clone()
+                    };
 
                     api.runtime.spawn(async move {
-                        let result = match list_servers(&api_base_url).await {
-                            Ok(servers) => Ok(servers),
-                            Err(_) => Err(()),
+                        let result = match list_servers(&api_base_url, &query).await {
+                            Ok(page) => Ok(page),
+                            Err(err) => Err(err.into()),
                         };
 
-                        tx.send(ApiMessage::LoadServersFulfilled(result))
+                        tx.send(ApiMessage::LoadServersFulfilled((append, result)))
                             .await
                             .unwrap()
                     });
@@ -242,7 +327,7 @@ fn api_event_handler_system(mut api: ResMut<ApiResource>, mut events: EventReade
                     api.runtime.spawn(async move {
                         let result = match get_user(&api_base_url, &id).await {
                             Ok(user) => Ok(user),
-                            Err(_) => Err(()),
+                            Err(err) => Err(err.into()),
                         };
 
                         tx.send(ApiMessage::LoadUserFulfilled((id, result)))
@@ -251,6 +336,29 @@ fn api_event_handler_system(mut api: ResMut<ApiResource>, mut events: EventReade
                     });
                 }
             }
+            ApiEvent::RequestJoinTicket(id) => {
+                if !api.join_ticket.loading {
+                    if let Some(auth_response) = &api.token.data {
+                        let tx = api.tx.clone();
+                        let api_base_url = api.base_url.clone();
+                        let token = auth_response.token.clone();
+                        let id = *id;
+
+                        api.join_ticket.start();
+
+                        api.runtime.spawn(async move {
+                            let result = match issue_join_ticket(&api_base_url, &token, &id).await {
+                                Ok(ticket) => Ok(ticket),
+                                Err(err) => Err(err.into()),
+                            };
+
+                            tx.send(ApiMessage::JoinTicketFulfilled((id, result)))
+                                .await
+                                .unwrap()
+                        });
+                    }
+                }
+            }
         }
     }
 }
@@ -258,46 +366,79 @@ fn api_event_handler_system(mut api: ResMut<ApiResource>, mut events: EventReade
 fn api_message_handler_system(
     mut api: ResMut<ApiResource>,
     mut events: EventWriter<ApiEvent>,
+    mut client_events: EventWriter<ClientEvent>,
     mut auth_state: ResMut<NextState<AuthState>>,
 ) {
     if let Ok(message) = api.rx.try_recv() {
         match message {
             ApiMessage::AuthenticateFulfilled(result) => match result {
-                Ok(token) => {
-                    api.token.finish(token);
+                Ok(auth_response) => {
+                    api.token.finish(auth_response);
                     auth_state.set(AuthState::Authenticated);
                     events.send(ApiEvent::LoadProfile);
                     events.send(ApiEvent::LoadServers);
                 }
-                Err(_) => {
-                    api.token.failed();
+                Err(error) => {
+                    api.token.failed(error);
                 }
             },
             ApiMessage::RegisterFulfilled(result) => match result {
-                Ok(token) => {
-                    api.token.finish(token);
+                Ok(auth_response) => {
+                    api.token.finish(auth_response);
                     auth_state.set(AuthState::Authenticated);
                     events.send(ApiEvent::LoadProfile);
                     events.send(ApiEvent::LoadServers);
                 }
-                Err(_) => {
-                    api.token.failed();
+                Err(error) => {
+                    api.token.failed(error);
+                }
+            },
+            ApiMessage::RefreshTokenFulfilled(result) => match result {
+                Ok(auth_response) => {
+                    api.token.finish(auth_response);
+                    api.refresh_retry_at = None;
+                }
+                // The session is still good when the api couldn't be reached, keep the token and
+                // try again in a bit.
+                Err(error) if error.code != ErrorCode::Unauthorized => {
+                    api.token.loading = false;
+                    api.token.error = Some(error);
+                    api.refresh_retry_at = Some(Instant::now() + REFRESH_RETRY_DELAY);
+                }
+                Err(error) => {
+                    api.token.failed(error);
+                    api.profile = LoadableData::default();
+                    auth_state.set(AuthState::Anonymous);
                 }
             },
             ApiMessage::LoadProfileFulfilled(result) => match result {
                 Ok(user) => {
                     api.profile.finish(user);
                 }
-                Err(_) => {
-                    api.profile.failed();
+                Err(error) => {
+                    api.profile.failed(error);
                 }
             },
-            ApiMessage::LoadServersFulfilled(result) => match result {
-                Ok(servers) => {
+            ApiMessage::LoadServersFulfilled((append, result)) => match result {
+                Ok(page) => {
+                    let mut servers = page.servers;
+
+                    if append {
+                        if let Some(loaded) = api.servers.data.take() {
+                            servers = loaded.into_iter().chain(servers).collect();
+                        }
+                    }
+
+                    api.next_servers_cursor = page.next_cursor;
                     api.servers.finish(servers);
                 }
-                Err(_) => {
-                    api.servers.failed();
+                // The servers already listed stay when a further page fails.
+                Err(error) if append => {
+                    api.servers.loading = false;
+                    api.servers.error = Some(error);
+                }
+                Err(error) => {
+                    api.servers.failed(error);
                 }
             },
             ApiMessage::LoadUserFulfilled((id, result)) => match result {
@@ -306,12 +447,36 @@ fn api_message_handler_system(
                         loadable.finish(user);
                     }
                 }
-                Err(_) => {
+                Err(error) => {
                     if let Some(loadable) = api.users.get_mut(&id) {
-                        loadable.failed();
+                        loadable.failed(error);
                     }
                 }
             },
+            ApiMessage::JoinTicketFulfilled((id, result)) => match result {
+                Ok(ticket) => {
+                    api.join_ticket.finish(ticket);
+                    client_events.send(ClientEvent::Connect(id));
+                }
+                Err(error) => {
+                    api.join_ticket.failed(error);
+                }
+            },
+        }
+    }
+}
+
+/// Refreshes the access token shortly before it expires so requests never run with a stale one.
+fn refresh_token_system(api: Res<ApiResource>, mut events: EventWriter<ApiEvent>) {
+    if let Some(auth_response) = &api.token.data {
+        let remaining = auth_response.expires_at - OffsetDateTime::now_utc();
+
+        let retry = api
+            .refresh_retry_at
+            .is_none_or(|retry_at| Instant::now() >= retry_at);
+
+        if !api.token.loading && retry && remaining < Duration::from_secs(60) {
+            events.send(ApiEvent::RefreshToken);
         }
     }
 }
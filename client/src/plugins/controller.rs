@@ -3,98 +3,67 @@ use crate::ConnectionState;
 use bevy::prelude::*;
 use bevy_quinnet::client::QuinnetClient;
 use engine::{
-    components::{
-        movement::{MoveModifier, Movement},
-        player::Player,
-    },
-    models::network::ClientMessage,
+    components::{movement::Movement, player::Player},
+    models::network::{ClientMessage, PlayerInput},
+    plugins::movement::MovementSet,
 };
 
+use super::prediction::PendingInputs;
+
 pub struct ControllerPlugin;
 
 impl Plugin for ControllerPlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(
-            Update,
-            keyboard_input.run_if(in_state(ConnectionState::Connected)),
+        app.init_resource::<InputSequence>().add_systems(
+            FixedUpdate,
+            send_input
+                .before(MovementSet)
+                .run_if(in_state(ConnectionState::Connected)),
         );
     }
 }
 
-fn keyboard_input(
-    mut players: Query<(&Player, &Transform, &mut Movement)>,
+/// The sequence of the last input sent to the server.
+#[derive(Default, Resource)]
+struct InputSequence(u32);
+
+/// Sends the movement keys held down to the server every tick, moving ahead with them until it
+/// tells us where that got us.
+fn send_input(
+    mut players: Query<(&Player, &mut Movement)>,
     api: Res<ApiResource>,
     keys: Res<ButtonInput<KeyCode>>,
-    client: ResMut<QuinnetClient>,
+    client: Res<QuinnetClient>,
+    mut sequence: ResMut<InputSequence>,
+    mut pending: ResMut<PendingInputs>,
 ) {
-    if let Some(user) = &api.profile.data {
-        if let Some((_, _, mut movement)) = players
-            .iter_mut()
-            .find(|(player, _, _)| player.user_id == user.id)
-        {
-            if keys.just_pressed(KeyCode::KeyW) {
-                let modifier = MoveModifier::StartForward;
-                movement.modify(modifier.clone());
-                client
-                    .connection()
-                    .send_message(ClientMessage::SendModifier(modifier))
-                    .unwrap();
-            } else if keys.just_released(KeyCode::KeyW) {
-                let modifier = MoveModifier::StopForward;
-                movement.modify(modifier.clone());
-                client
-                    .connection()
-                    .send_message(ClientMessage::SendModifier(modifier))
-                    .unwrap();
-            }
+    let Some(user) = &api.profile.data else {
+        return;
+    };
 
-            if keys.just_pressed(KeyCode::KeyS) {
-                let modifier = MoveModifier::StartBackward;
-                movement.modify(modifier.clone());
-                client
-                    .connection()
-                    .send_message(ClientMessage::SendModifier(modifier))
-                    .unwrap();
-            } else if keys.just_released(KeyCode::KeyS) {
-                let modifier = MoveModifier::StopBackward;
-                movement.modify(modifier.clone());
-                client
-                    .connection()
-                    .send_message(ClientMessage::SendModifier(modifier))
-                    .unwrap();
-            }
+    let Some((_, mut movement)) = players
+        .iter_mut()
+        .find(|(player, _)| player.user_id == user.id)
+    else {
+        return;
+    };
 
-            if keys.just_pressed(KeyCode::KeyD) {
-                let modifier = MoveModifier::StartRight;
-                movement.modify(modifier.clone());
-                client
-                    .connection()
-                    .send_message(ClientMessage::SendModifier(modifier))
-                    .unwrap();
-            } else if keys.just_released(KeyCode::KeyD) {
-                let modifier = MoveModifier::StopRight;
-                movement.modify(modifier.clone());
-                client
-                    .connection()
-                    .send_message(ClientMessage::SendModifier(modifier))
-                    .unwrap();
-            }
+    *movement = Movement {
+        forward: keys.pressed(KeyCode::KeyW),
+        backward: keys.pressed(KeyCode::KeyS),
+        right: keys.pressed(KeyCode::KeyD),
+        left: keys.pressed(KeyCode::KeyA),
+    };
 
-            if keys.just_pressed(KeyCode::KeyA) {
-                let modifier = MoveModifier::StartLeft;
-                movement.modify(modifier.clone());
-                client
-                    .connection()
-                    .send_message(ClientMessage::SendModifier(modifier))
-                    .unwrap();
-            } else if keys.just_released(KeyCode::KeyA) {
-                let modifier = MoveModifier::StopLeft;
-                movement.modify(modifier.clone());
-                client
-                    .connection()
-                    .send_message(ClientMessage::SendModifier(modifier))
-                    .unwrap();
-            }
-        }
-    }
+    sequence.0 = sequence.0.wrapping_add(1);
+
+    let input = PlayerInput {
+        sequence: sequence.0,
+        movement: *movement,
+    };
+
+    pending.push(input);
+    client
+        .connection()
+        .try_send_message(ClientMessage::Input(input));
 }
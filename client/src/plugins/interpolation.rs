@@ -0,0 +1,142 @@
+use bevy::prelude::*;
+use engine::{components::player::PlayerPosition, plugins::movement::Tick};
+use std::{collections::VecDeque, time::Duration};
+
+use crate::{components::controllable::Controllable, ConnectionState};
+
+/// Snapshots kept per player, about a second worth.
+const MAX_SNAPSHOTS: usize = 64;
+
+/// How long a player keeps moving the way it was when its snapshots stop coming, in seconds.
+const MAX_EXTRAPOLATION: f64 = 0.25;
+
+/// How far off the server clock can drift before it's set instead of nudged, in seconds.
+const CLOCK_RESYNC: f64 = 0.25;
+
+/// How much of the drift of the server clock is corrected per snapshot.
+const CLOCK_SMOOTHING: f64 = 0.1;
+
+/// Shows other players a little in the past, between the snapshots the server sent of them.
+pub struct InterpolationPlugin {
+    delay: Duration,
+}
+
+impl InterpolationPlugin {
+    pub fn new(delay: Duration) -> Self {
+        Self { delay }
+    }
+}
+
+impl Plugin for InterpolationPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(InterpolationDelay(self.delay))
+            .init_resource::<ServerClock>()
+            .add_systems(Update, interpolate)
+            .add_systems(OnExit(ConnectionState::Connected), reset_server_clock);
+    }
+}
+
+/// How far in the past other players are shown.
+#[derive(Resource)]
+pub struct InterpolationDelay(pub Duration);
+
+/// The time of the server as far as we can tell, in seconds since its first tick.
+#[derive(Default, Resource)]
+pub struct ServerClock(Option<f64>);
+
+impl ServerClock {
+    /// Pulls the clock towards the time of a snapshot that just arrived.
+    pub fn observe(&mut self, tick: u64) {
+        let sent_at = Tick::seconds(tick);
+
+        self.0 = Some(match self.0 {
+            Some(now) if (sent_at - now).abs() <= CLOCK_RESYNC => {
+                now + (sent_at - now) * CLOCK_SMOOTHING
+            }
+            _ => sent_at,
+        });
+    }
+}
+
+struct Snapshot {
+    time: f64,
+    position: Vec3,
+}
+
+/// The positions the server sent of a player, oldest first.
+#[derive(Default, Component)]
+pub struct SnapshotBuffer {
+    snapshots: VecDeque<Snapshot>,
+    /// How fast the player moved between its last two snapshots, per second
+    velocity: Vec3,
+}
+
+impl SnapshotBuffer {
+    pub fn push(&mut self, tick: u64, position: Vec3) {
+        let time = Tick::seconds(tick);
+
+        if let Some(last) = self.snapshots.back() {
+            // Snapshots can arrive twice or out of order.
+            if time <= last.time {
+                return;
+            }
+
+            self.velocity = (position - last.position) / (time - last.time) as f32;
+        }
+
+        if self.snapshots.len() >= MAX_SNAPSHOTS {
+            self.snapshots.pop_front();
+        }
+
+        self.snapshots.push_back(Snapshot { time, position });
+    }
+
+    /// Where the player was at `time`, carrying on past the newest snapshot for a while.
+    fn sample(&mut self, time: f64) -> Option<Vec3> {
+        // Only the last snapshot before `time` is needed from here on.
+        while self.snapshots.get(1).is_some_and(|next| next.time <= time) {
+            self.snapshots.pop_front();
+        }
+
+        let from = self.snapshots.front()?;
+
+        if time <= from.time {
+            return Some(from.position);
+        }
+
+        match self.snapshots.get(1) {
+            Some(to) => {
+                let t = (time - from.time) / (to.time - from.time);
+                Some(from.position.lerp(to.position, t as f32))
+            }
+            None => {
+                let ahead = (time - from.time).min(MAX_EXTRAPOLATION);
+                Some(from.position + self.velocity * ahead as f32)
+            }
+        }
+    }
+}
+
+fn interpolate(
+    mut players: Query<(&mut PlayerPosition, &mut SnapshotBuffer), Without<Controllable>>,
+    mut clock: ResMut<ServerClock>,
+    delay: Res<InterpolationDelay>,
+    time: Res<Time>,
+) {
+    let Some(now) = clock.0.as_mut() else {
+        return;
+    };
+
+    *now += time.delta_seconds_f64();
+    let render_time = *now - delay.0.as_secs_f64();
+
+    for (mut position, mut buffer) in players.iter_mut() {
+        if let Some(sampled) = buffer.sample(render_time) {
+            position.0 = sampled;
+        }
+    }
+}
+
+fn reset_server_clock(mut clock: ResMut<ServerClock>) {
+    clock.0 = None;
+}
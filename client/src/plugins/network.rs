@@ -1,22 +1,25 @@
 use bevy::prelude::*;
 use bevy_quinnet::{
     client::{
-        certificate::CertificateVerificationMode, connection::ClientEndpointConfiguration,
+        certificate::CertificateVerificationMode,
+        connection::{ClientEndpointConfiguration, ConnectionLostEvent},
         QuinnetClient, QuinnetClientPlugin,
     },
     shared::{channels::ChannelsConfiguration, ClientId},
 };
-use engine::models::network::{ClientMessage, ServerMessage};
+use engine::models::network::{ChatChannel, ClientMessage, ServerMessage, HEARTBEAT_INTERVAL};
 use std::{
-    collections::HashMap,
+    collections::{HashMap, HashSet},
     net::{IpAddr, Ipv4Addr},
 };
+use time::OffsetDateTime;
 use uuid::Uuid;
 
 use crate::{AuthState, ClientEvent, ConnectionState};
 
 use super::{
     api::{ApiEvent, ApiResource},
+    prediction::ReconcileEvent,
     render::RenderEvent,
 };
 
@@ -26,8 +29,20 @@ impl Plugin for NetworkPlugin {
     fn build(&self, app: &mut App) {
         app.add_plugins(QuinnetClientPlugin::default())
             .init_resource::<ServerInfo>()
+            .insert_resource(HeartbeatTimer(Timer::new(
+                HEARTBEAT_INTERVAL,
+                TimerMode::Repeating,
+            )))
             .add_systems(Update, event_system)
+            .add_systems(
+                Update,
+                heartbeat_system.run_if(in_state(ConnectionState::Connected)),
+            )
             .add_systems(Last, handle_disconnect)
+            .add_systems(
+                Update,
+                handle_connection_lost.run_if(in_state(ConnectionState::Connected)),
+            )
             .add_systems(
                 Update,
                 handle_server_messages
@@ -41,19 +56,72 @@ impl Plugin for NetworkPlugin {
 pub struct ServerInfo {
     pub id: Option<Uuid>,
     pub connected: HashMap<ClientId, Uuid>,
-    pub messages: Vec<(ClientId, String)>,
+    pub messages: Vec<ChatEntry>,
+    /// Whether the server has older messages than the first of `messages`
+    pub more_history: bool,
+    /// The sequence number of the oldest message the server sent as history
+    pub history_cursor: Option<u64>,
+    /// Why the last connection ended, shown until dismissed
+    pub disconnect_reason: Option<String>,
+}
+
+pub struct ChatEntry {
+    /// Absent for messages from the server itself
+    pub user_id: Option<Uuid>,
+    pub channel: ChatChannel,
+    pub message: String,
+    pub sent_at: OffsetDateTime,
+}
+
+#[derive(Resource)]
+struct HeartbeatTimer(Timer);
+
+fn heartbeat_system(
+    client: Res<QuinnetClient>,
+    time: Res<Time>,
+    mut timer: ResMut<HeartbeatTimer>,
+) {
+    if timer.0.tick(time.delta()).just_finished() {
+        if let Some(connection) = client.get_connection() {
+            connection.try_send_message(ClientMessage::Heartbeat);
+        }
+    }
 }
 
 fn handle_server_messages(
     mut api_events: EventWriter<ApiEvent>,
     mut client: ResMut<QuinnetClient>,
+    mut next_connection_state: ResMut<NextState<ConnectionState>>,
     mut server_info: ResMut<ServerInfo>,
     mut render_events: EventWriter<RenderEvent>,
+    mut reconcile_events: EventWriter<ReconcileEvent>,
 ) {
     while let Ok(Some((_channel_id, message))) =
         client.connection_mut().receive_message::<ServerMessage>()
     {
         match message {
+            ServerMessage::JoinRejected { reason } => {
+                warn!("Join rejected: {reason}");
+                leave_server(
+                    &mut client,
+                    &mut server_info,
+                    &mut render_events,
+                    &mut next_connection_state,
+                    reason,
+                );
+                return;
+            }
+            ServerMessage::Kicked { reason } => {
+                warn!("Kicked: {reason}");
+                leave_server(
+                    &mut client,
+                    &mut server_info,
+                    &mut render_events,
+                    &mut next_connection_state,
+                    reason.to_string(),
+                );
+                return;
+            }
             ServerMessage::ClientConnected { client_id, user_id } => {
                 server_info.connected.insert(client_id, user_id);
                 api_events.send(ApiEvent::LoadUser(user_id));
@@ -63,27 +131,68 @@ fn handle_server_messages(
                 server_info.connected.remove(&client_id);
                 render_events.send(RenderEvent::Despawn(client_id));
             }
-            ServerMessage::ChatMessage { client_id, message } => {
-                server_info.messages.push((client_id, message));
-            }
-            ServerMessage::UpdatePosition {
+            ServerMessage::ChatMessage {
                 client_id,
-                position,
+                channel,
+                message,
+                sent_at,
             } => {
-                render_events.send(RenderEvent::UpdatePosition {
-                    client_id,
-                    position,
+                let user_id =
+                    client_id.and_then(|client_id| server_info.connected.get(&client_id).copied());
+
+                server_info.messages.push(ChatEntry {
+                    user_id,
+                    channel,
+                    message,
+                    sent_at,
                 });
             }
-            ServerMessage::SendModifier {
-                client_id,
-                modifier: key_code,
-            } => {
-                render_events.send(RenderEvent::UpdateMovement {
-                    client_id,
-                    modifier: key_code,
+            ServerMessage::ChatHistory { messages, more } => {
+                // Senders that left since aren't loaded with the connected players.
+                let unknown: HashSet<Uuid> = messages
+                    .iter()
+                    .filter_map(|entry| entry.user_id)
+                    .filter(|user_id| !server_info.connected.values().any(|id| id == user_id))
+                    .collect();
+
+                for user_id in unknown {
+                    api_events.send(ApiEvent::LoadUser(user_id));
+                }
+
+                if let Some(oldest) = messages.first() {
+                    server_info.history_cursor = Some(oldest.seq);
+                }
+
+                let entries = messages.into_iter().map(|entry| ChatEntry {
+                    user_id: entry.user_id,
+                    channel: entry.channel,
+                    message: entry.message,
+                    sent_at: entry.sent_at,
                 });
+
+                server_info.messages.splice(0..0, entries);
+                server_info.more_history = more;
             }
+            ServerMessage::UpdatePosition {
+                client_id,
+                position,
+                input_sequence,
+                tick,
+            } => match client.connection().client_id() == Some(client_id) {
+                true => {
+                    reconcile_events.send(ReconcileEvent {
+                        position,
+                        input_sequence,
+                    });
+                }
+                false => {
+                    render_events.send(RenderEvent::UpdatePosition {
+                        client_id,
+                        position,
+                        tick,
+                    });
+                }
+            },
         }
     }
 }
@@ -132,11 +241,53 @@ fn event_system(
     }
 }
 
+fn handle_connection_lost(
+    mut client: ResMut<QuinnetClient>,
+    mut connection_lost_events: EventReader<ConnectionLostEvent>,
+    mut server_info: ResMut<ServerInfo>,
+    mut render_events: EventWriter<RenderEvent>,
+    mut next_connection_state: ResMut<NextState<ConnectionState>>,
+) {
+    if connection_lost_events.read().count() > 0 {
+        leave_server(
+            &mut client,
+            &mut server_info,
+            &mut render_events,
+            &mut next_connection_state,
+            String::from("The connection to the server was lost"),
+        );
+    }
+}
+
+/// Closes the connection the server ended and returns to the server browser.
+fn leave_server(
+    client: &mut QuinnetClient,
+    server_info: &mut ServerInfo,
+    render_events: &mut EventWriter<RenderEvent>,
+    next_connection_state: &mut NextState<ConnectionState>,
+    reason: String,
+) {
+    for (client_id, _) in server_info.connected.drain() {
+        render_events.send(RenderEvent::Despawn(client_id));
+    }
+
+    server_info.id = None;
+    server_info.messages.clear();
+    server_info.more_history = false;
+    server_info.history_cursor = None;
+    server_info.disconnect_reason = Some(reason);
+
+    if let Err(err) = client.close_all_connections() {
+        warn!("Could not close connection: {err}");
+    }
+
+    next_connection_state.set(ConnectionState::Disconnected);
+}
+
 fn handle_disconnect(client: Res<QuinnetClient>, mut app_exit_event_reader: EventReader<AppExit>) {
     for _ in app_exit_event_reader.read() {
-        client
-            .connection()
-            .send_message(engine::models::network::ClientMessage::Disconnect)
-            .unwrap();
+        if let Some(connection) = client.get_connection() {
+            connection.try_send_message(ClientMessage::Disconnect);
+        }
     }
 }
@@ -0,0 +1,95 @@
+use bevy::prelude::*;
+use engine::{
+    components::player::PlayerPosition, models::network::PlayerInput, plugins::movement::step,
+};
+use std::collections::VecDeque;
+
+use crate::{components::controllable::Controllable, ConnectionState};
+
+/// Inputs kept while the server hasn't acknowledged them, about four seconds worth.
+const MAX_PENDING_INPUTS: usize = 256;
+
+/// Corrections further than this are snapped to instead of smoothed.
+const SNAP_DISTANCE: f32 = 2.0;
+
+/// How fast a smoothed correction fades, per second.
+const CORRECTION_RATE: f32 = 10.0;
+
+pub struct PredictionPlugin;
+
+impl Plugin for PredictionPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<ReconcileEvent>()
+            .init_resource::<PendingInputs>()
+            .add_systems(Update, (reconcile, fade_correction).chain())
+            .add_systems(OnExit(ConnectionState::Connected), clear_pending_inputs);
+    }
+}
+
+/// The position the server has for the local player.
+#[derive(Event)]
+pub struct ReconcileEvent {
+    pub position: Vec3,
+    /// The last input of ours the server applied to `position`
+    pub input_sequence: u32,
+}
+
+/// The inputs sent to the server that it hasn't applied yet, oldest first.
+#[derive(Default, Resource)]
+pub struct PendingInputs(VecDeque<PlayerInput>);
+
+impl PendingInputs {
+    pub fn push(&mut self, input: PlayerInput) {
+        if self.0.len() >= MAX_PENDING_INPUTS {
+            self.0.pop_front();
+        }
+
+        self.0.push_back(input);
+    }
+}
+
+/// How far the local player is shown from its predicted position, left over from the last
+/// correction by the server and fading away.
+#[derive(Default, Component)]
+pub struct Correction(pub Vec3);
+
+/// Starts over from the position of the server, replaying the inputs it hasn't applied yet.
+fn reconcile(
+    mut events: EventReader<ReconcileEvent>,
+    mut pending: ResMut<PendingInputs>,
+    mut players: Query<(&mut PlayerPosition, &mut Correction), With<Controllable>>,
+) {
+    for event in events.read() {
+        pending
+            .0
+            .retain(|input| input.sequence > event.input_sequence);
+
+        let predicted = pending.0.iter().fold(event.position, |position, input| {
+            step(position, &input.movement)
+        });
+
+        let Ok((mut position, mut correction)) = players.get_single_mut() else {
+            continue;
+        };
+
+        let error = position.0 + correction.0 - predicted;
+
+        correction.0 = match error.length() > SNAP_DISTANCE {
+            true => Vec3::ZERO,
+            false => error,
+        };
+        position.0 = predicted;
+    }
+}
+
+fn fade_correction(mut corrections: Query<&mut Correction>, time: Res<Time>) {
+    let remaining = (-CORRECTION_RATE * time.delta_seconds()).exp();
+
+    for mut correction in corrections.iter_mut() {
+        correction.0 *= remaining;
+    }
+}
+
+fn clear_pending_inputs(mut pending: ResMut<PendingInputs>) {
+    pending.0.clear();
+}
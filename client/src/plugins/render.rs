@@ -1,14 +1,18 @@
 use bevy::prelude::*;
 use bevy_quinnet::shared::ClientId;
 use engine::components::{
-    movement::{MoveModifier, Movement},
+    movement::Movement,
     player::{Player, PlayerPosition},
 };
 use uuid::Uuid;
 
 use crate::components::controllable::Controllable;
 
-use super::api::ApiResource;
+use super::{
+    api::ApiResource,
+    interpolation::{ServerClock, SnapshotBuffer},
+    prediction::Correction,
+};
 
 pub struct RenderPlugin;
 
@@ -32,13 +36,11 @@ pub enum RenderEvent {
         user_id: Uuid,
     },
     Despawn(ClientId),
+    /// A snapshot of a player other than ours
     UpdatePosition {
         client_id: ClientId,
         position: Vec3,
-    },
-    UpdateMovement {
-        client_id: ClientId,
-        modifier: MoveModifier,
+        tick: u64,
     },
 }
 
@@ -76,7 +78,8 @@ fn setup(
 
 fn handle_render_event(
     api: Res<ApiResource>,
-    mut players: Query<(Entity, &Player, &mut PlayerPosition, &mut Movement)>,
+    mut players: Query<(Entity, &Player, Option<&mut SnapshotBuffer>)>,
+    mut clock: ResMut<ServerClock>,
     mut events: EventReader<RenderEvent>,
     mut commands: Commands,
     mut meshes: ResMut<Assets<Mesh>>,
@@ -100,16 +103,15 @@ fn handle_render_event(
                     },
                 ));
 
-                if let Some(user) = &api.profile.data {
-                    if &user.id == user_id {
-                        entity.insert(Controllable);
-                    }
-                }
+                match api.profile.data.as_ref().map(|user| user.id) == Some(*user_id) {
+                    true => entity.insert((Controllable, Correction::default())),
+                    false => entity.insert(SnapshotBuffer::default()),
+                };
             }
             RenderEvent::Despawn(client_id) => {
-                if let Some((entity, _, _, _)) = players
-                    .into_iter()
-                    .find(|(_, player, _, _)| player.client_id == *client_id)
+                if let Some((entity, _, _)) = players
+                    .iter()
+                    .find(|(_, player, _)| player.client_id == *client_id)
                 {
                     commands.entity(entity).despawn();
                 }
@@ -117,47 +119,36 @@ fn handle_render_event(
             RenderEvent::UpdatePosition {
                 client_id,
                 position,
+                tick,
             } => {
-                if let Some((_, _, mut player_position, _)) = players
-                    .iter_mut()
-                    .find(|(_, player, _, _)| player.client_id == *client_id)
-                {
-                    player_position.0.x = position.x;
-                    player_position.0.y = position.y;
-                    player_position.0.z = position.z;
-                }
-            }
-            RenderEvent::UpdateMovement {
-                client_id,
-                modifier,
-            } => {
-                if let Some((_, _, _, mut movement)) = players
+                clock.observe(*tick);
+
+                if let Some((_, _, Some(mut buffer))) = players
                     .iter_mut()
-                    .find(|(_, player, _, _)| player.client_id == *client_id)
+                    .find(|(_, player, _)| player.client_id == *client_id)
                 {
-                    movement.modify(modifier.clone());
+                    buffer.push(*tick, *position);
                 }
             }
         }
     }
 }
 
-fn update_position(mut players: Query<(&mut Transform, &PlayerPosition)>) {
-    for (mut transform, player_position) in players.iter_mut() {
-        transform.translation.x = player_position.0.x;
-        transform.translation.y = player_position.0.y;
-        transform.translation.z = player_position.0.z;
+fn update_position(mut players: Query<(&mut Transform, &PlayerPosition, Option<&Correction>)>) {
+    for (mut transform, player_position, correction) in players.iter_mut() {
+        transform.translation =
+            player_position.0 + correction.map_or(Vec3::ZERO, |correction| correction.0);
     }
 }
 
 fn update_camera(
-    controllable: Query<(&Controllable, &PlayerPosition)>,
+    controllable: Query<(&Controllable, &PlayerPosition, &Correction)>,
     mut camera: Query<(&mut Transform, &CameraMarker)>,
 ) {
     let camera_offset = Vec3::new(-10.0, 10.0, 0.0);
 
-    for (_, position) in controllable.iter() {
-        let player_pos = position.0;
+    for (_, position, correction) in controllable.iter() {
+        let player_pos = position.0 + correction.0;
 
         for (mut transform, _) in camera.iter_mut() {
             transform.translation = player_pos + camera_offset;
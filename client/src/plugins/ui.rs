@@ -6,7 +6,10 @@ use crate::{AuthState, ClientEvent, ConnectionState};
 use bevy::prelude::*;
 use bevy_egui::{egui, EguiContexts, EguiPlugin};
 use bevy_quinnet::client::QuinnetClient;
-use engine::models::network::ClientMessage;
+use engine::models::network::{ChatChannel, ClientMessage};
+use models::api::servers::ServerSort;
+use time::UtcOffset;
+use uuid::Uuid;
 
 pub struct UiPlugin;
 
@@ -28,7 +31,7 @@ impl Plugin for UiPlugin {
             )
             .add_systems(
                 Update,
-                server_browser_ui_system
+                (server_browser_ui_system, disconnect_dialog_system)
                     .run_if(in_state(AuthState::Authenticated))
                     .run_if(in_state(ConnectionState::Disconnected)),
             );
@@ -51,9 +54,67 @@ struct RegisterInputState {
 #[derive(Default, Resource)]
 struct ChatInputState {
     text: String,
+    tab: ChatTab,
+    whisper_to: Option<Uuid>,
+}
+
+/// The offset of the local time zone, to show chat timestamps in.
+#[derive(Resource)]
+pub struct LocalOffset(pub UtcOffset);
+
+#[derive(Default, Clone, Copy, PartialEq)]
+enum ChatTab {
+    #[default]
+    All,
+    Global,
+    Team,
+    Whisper,
+    System,
+}
+
+impl ChatTab {
+    const ALL: [ChatTab; 5] = [
+        ChatTab::All,
+        ChatTab::Global,
+        ChatTab::Team,
+        ChatTab::Whisper,
+        ChatTab::System,
+    ];
+
+    fn label(&self) -> &'static str {
+        match self {
+            ChatTab::All => "All",
+            ChatTab::Global => "Global",
+            ChatTab::Team => "Team",
+            ChatTab::Whisper => "Whispers",
+            ChatTab::System => "System",
+        }
+    }
+
+    fn shows(&self, channel: &ChatChannel) -> bool {
+        matches!(
+            (self, channel),
+            (ChatTab::All, _)
+                | (ChatTab::Global, ChatChannel::Global)
+                | (ChatTab::Team, ChatChannel::Team)
+                | (ChatTab::Whisper, ChatChannel::Whisper(_))
+                | (ChatTab::System, ChatChannel::System)
+        )
+    }
+
+    /// Where messages typed on this tab go, nowhere for tabs players can't send to.
+    fn channel(&self, whisper_to: Option<Uuid>) -> Option<ChatChannel> {
+        match self {
+            ChatTab::All | ChatTab::Global => Some(ChatChannel::Global),
+            ChatTab::Team => Some(ChatChannel::Team),
+            ChatTab::Whisper => whisper_to.map(ChatChannel::Whisper),
+            ChatTab::System => None,
+        }
+    }
 }
 
 fn auth_ui_system(
+    api: Res<ApiResource>,
     mut api_events: EventWriter<ApiEvent>,
     mut contexts: EguiContexts,
     mut login_input_state: ResMut<LoginInputState>,
@@ -93,6 +154,15 @@ fn auth_ui_system(
             });
         }
     });
+
+    if let Some(error) = &api.token.error {
+        egui::Window::new("Error").show(contexts.ctx_mut(), |ui| {
+            ui.colored_label(egui::Color32::RED, &error.message);
+            for field in error.fields.iter() {
+                ui.label(format!("{}: {}", field.field, field.message));
+            }
+        });
+    }
 }
 
 fn server_ui_system(
@@ -102,6 +172,7 @@ fn server_ui_system(
     server_info: Res<ServerInfo>,
     mut chat_input_state: ResMut<ChatInputState>,
     client: Res<QuinnetClient>,
+    local_offset: Res<LocalOffset>,
 ) {
     egui::Window::new("Server").show(contexts.ctx_mut(), |ui| {
         ui.label("Connected");
@@ -109,71 +180,204 @@ fn server_ui_system(
             client_event_writer.send(ClientEvent::Disconnect);
         }
         ui.label("Connected users:");
-        for (_client_id, user_id) in server_info.connected.iter() {
-            let mut username = format!("{}", user_id);
-
-            if let Some(loadable) = api.users.get(user_id) {
-                if let Some(user) = &loadable.data {
-                    username = user.username.clone();
-                }
-            }
-
-            ui.label(username.to_string());
+        for user_id in server_info.connected.values() {
+            ui.label(user_name(&api, user_id));
         }
     });
 
+    let own_user_id = api.profile.data.as_ref().map(|user| user.id);
+
     egui::Window::new("Chat").show(contexts.ctx_mut(), |ui| {
-        for (client_id, message) in server_info.messages.iter() {
-            let mut username = format!("{}", client_id);
+        ui.horizontal(|ui| {
+            for tab in ChatTab::ALL {
+                ui.selectable_value(&mut chat_input_state.tab, tab, tab.label());
+            }
+        });
 
-            if let Some(user_id) = server_info.connected.get(client_id) {
-                username = format!("{}", user_id);
+        ui.separator();
 
-                if let Some(loadable) = api.users.get(user_id) {
-                    if let Some(user) = &loadable.data {
-                        username = user.username.clone();
+        egui::ScrollArea::vertical()
+            .max_height(200.0)
+            .stick_to_bottom(true)
+            .show(ui, |ui| {
+                if let (true, Some(before)) = (server_info.more_history, server_info.history_cursor)
+                {
+                    if ui.button("Load older messages").clicked() {
+                        client
+                            .connection()
+                            .send_message(ClientMessage::RequestChatHistory { before })
+                            .unwrap();
                     }
                 }
-            }
 
-            ui.label(format!("{}: {}", username, message));
+                for entry in server_info
+                    .messages
+                    .iter()
+                    .filter(|entry| chat_input_state.tab.shows(&entry.channel))
+                {
+                    let sent_at = entry.sent_at.to_offset(local_offset.0);
+                    let time = format!("[{:02}:{:02}]", sent_at.hour(), sent_at.minute());
+
+                    let sender = entry.user_id.map(|user_id| user_name(&api, &user_id));
+
+                    let (color, text) = match (entry.channel, sender) {
+                        (ChatChannel::System, _) | (_, None) => {
+                            (egui::Color32::YELLOW, format!("{time} {}", entry.message))
+                        }
+                        (ChatChannel::Global, Some(sender)) => (
+                            egui::Color32::WHITE,
+                            format!("{time} {sender}: {}", entry.message),
+                        ),
+                        (ChatChannel::Team, Some(sender)) => (
+                            egui::Color32::LIGHT_GREEN,
+                            format!("{time} [Team] {sender}: {}", entry.message),
+                        ),
+                        (ChatChannel::Whisper(user_id), Some(sender)) => (
+                            egui::Color32::from_rgb(200, 150, 255),
+                            format!(
+                                "{time} {sender} -> {}: {}",
+                                user_name(&api, &user_id),
+                                entry.message
+                            ),
+                        ),
+                    };
+
+                    ui.colored_label(color, text);
+                }
+            });
+
+        ui.separator();
+
+        if chat_input_state.tab == ChatTab::Whisper {
+            let selected = chat_input_state
+                .whisper_to
+                .map_or(String::from("Choose a player"), |user_id| {
+                    user_name(&api, &user_id)
+                });
+
+            egui::ComboBox::from_label("To")
+                .selected_text(selected)
+                .show_ui(ui, |ui| {
+                    for user_id in server_info
+                        .connected
+                        .values()
+                        .filter(|user_id| Some(**user_id) != own_user_id)
+                    {
+                        ui.selectable_value(
+                            &mut chat_input_state.whisper_to,
+                            Some(*user_id),
+                            user_name(&api, user_id),
+                        );
+                    }
+                });
         }
 
+        let Some(channel) = chat_input_state.tab.channel(chat_input_state.whisper_to) else {
+            return;
+        };
+
         ui.text_edit_singleline(&mut chat_input_state.text);
-        if ui.button("Send").clicked() {
+        if ui.button("Send").clicked() && !chat_input_state.text.trim().is_empty() {
             let message = chat_input_state.text.clone();
             client
                 .connection()
-                .send_message(ClientMessage::ChatMessage { message })
+                .send_message(ClientMessage::ChatMessage { channel, message })
                 .unwrap();
             chat_input_state.text = String::from("");
         }
     });
 }
 
+/// The username of a user, its id until the user has been loaded.
+fn user_name(api: &ApiResource, user_id: &Uuid) -> String {
+    api.users
+        .get(user_id)
+        .and_then(|loadable| loadable.data.as_ref())
+        .map_or(user_id.to_string(), |user| user.username.clone())
+}
+
 fn server_browser_ui_system(
-    api: Res<ApiResource>,
+    mut api: ResMut<ApiResource>,
     mut api_event_writer: EventWriter<ApiEvent>,
     mut contexts: EguiContexts,
-    mut client_event_writer: EventWriter<ClientEvent>,
 ) {
     egui::Window::new("Servers").show(contexts.ctx_mut(), |ui| {
         if let Some(user) = &api.profile.data {
             ui.label(format!("user_id: {}", user.id));
         }
 
+        if ui.button("Logout").clicked() {
+            api_event_writer.send(ApiEvent::Logout);
+        }
+
+        ui.horizontal(|ui| {
+            ui.label("Search:");
+            let search = api.server_query.search.get_or_insert_with(String::new);
+            ui.text_edit_singleline(search);
+        });
+
+        ui.horizontal(|ui| {
+            ui.checkbox(&mut api.server_query.not_full, "Not full");
+            ui.checkbox(&mut api.server_query.has_players, "Has players");
+
+            egui::ComboBox::from_label("Sort")
+                .selected_text(format!("{:?}", api.server_query.sort))
+                .show_ui(ui, |ui| {
+                    for sort in [ServerSort::LastPing, ServerSort::Players, ServerSort::Name] {
+                        ui.selectable_value(&mut api.server_query.sort, sort, format!("{sort:?}"));
+                    }
+                });
+        });
+
+        if api
+            .server_query
+            .search
+            .as_ref()
+            .is_some_and(|search| search.is_empty())
+        {
+            api.server_query.search = None;
+        }
+
         if let Some(servers) = &api.servers.data {
             for server in servers.iter() {
+                let status = &server.status;
+
                 ui.label(format!("Server name: {}:{}", server.name, server.port));
                 ui.label(server.addr.to_string());
+                ui.label(format!(
+                    "Players: {}/{}",
+                    status.player_count, status.max_players
+                ));
+
+                let details: Vec<&str> = [&status.game_mode, &status.map, &status.version]
+                    .into_iter()
+                    .flatten()
+                    .map(String::as_str)
+                    .collect();
+
+                if !details.is_empty() {
+                    ui.label(details.join(" | "));
+                }
+
+                if !status.tags.is_empty() {
+                    ui.label(format!("Tags: {}", status.tags.join(", ")));
+                }
+
                 if ui.button("Connect").clicked() {
-                    client_event_writer.send(ClientEvent::Connect(server.id));
+                    api_event_writer.send(ApiEvent::RequestJoinTicket(server.id));
                 }
             }
         } else {
             ui.label("No servers");
         }
 
+        for error in [&api.servers.error, &api.join_ticket.error]
+            .into_iter()
+            .flatten()
+        {
+            ui.colored_label(egui::Color32::RED, &error.message);
+        }
+
         let bttn_text = match api.servers.loading {
             true => "Loading...",
             false => "Reload",
@@ -182,5 +386,36 @@ fn server_browser_ui_system(
         if ui.button(bttn_text).clicked && !api.servers.loading {
             api_event_writer.send(ApiEvent::LoadServers);
         }
+
+        if api.next_servers_cursor.is_some()
+            && !api.servers.loading
+            && ui.button("Load more").clicked()
+        {
+            api_event_writer.send(ApiEvent::LoadMoreServers);
+        }
     });
 }
+
+fn disconnect_dialog_system(mut contexts: EguiContexts, mut server_info: ResMut<ServerInfo>) {
+    let Some(reason) = &server_info.disconnect_reason else {
+        return;
+    };
+
+    let mut dismissed = false;
+
+    egui::Window::new("Disconnected")
+        .collapsible(false)
+        .resizable(false)
+        .anchor(egui::Align2::CENTER_CENTER, egui::Vec2::ZERO)
+        .show(contexts.ctx_mut(), |ui| {
+            ui.label(reason);
+
+            if ui.button("OK").clicked() {
+                dismissed = true;
+            }
+        });
+
+    if dismissed {
+        server_info.disconnect_reason = None;
+    }
+}
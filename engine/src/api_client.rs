@@ -1,12 +1,21 @@
-use anyhow::Result;
+use josekit::jwk::JwkSet;
 use models::api::{
-    auth::{AuthResponse, Credentials},
-    servers::{RegisterServer, Server},
+    auth::{AuthResponse, Credentials, RefreshRequest, Session},
+    error::{ApiError, ErrorCode},
+    servers::{
+        JoinTicket, RegisterServer, RegisteredServer, Server, ServerPage, ServerQuery,
+        ServerStatus, UpdateServer,
+    },
+    user_bans::{BanCheck, UserBan},
     users::{NewUser, User},
 };
 use once_cell::sync::Lazy;
-use reqwest::{Client, Method};
-use std::time::Duration;
+use reqwest::{Client, Method, Response};
+use serde::de::DeserializeOwned;
+use std::{
+    fmt::{self, Display, Formatter},
+    time::Duration,
+};
 use uuid::Uuid;
 
 static CLIENT: Lazy<Client> = Lazy::new(|| {
@@ -16,6 +25,76 @@ static CLIENT: Lazy<Client> = Lazy::new(|| {
         .expect("failed to initialize client")
 });
 
+#[derive(Debug)]
+pub enum Error {
+    /// The api responded with an error
+    Api(ApiError),
+    /// The api could not be reached or sent an unreadable response
+    Request(reqwest::Error),
+    InvalidKeySet(josekit::JoseError),
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Api(err) => write!(f, "{err}"),
+            Error::Request(err) => write!(f, "request failed: {err}"),
+            Error::InvalidKeySet(err) => write!(f, "invalid key set: {err}"),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<reqwest::Error> for Error {
+    fn from(value: reqwest::Error) -> Self {
+        Self::Request(value)
+    }
+}
+
+impl From<josekit::JoseError> for Error {
+    fn from(value: josekit::JoseError) -> Self {
+        Self::InvalidKeySet(value)
+    }
+}
+
+impl From<Error> for ApiError {
+    fn from(value: Error) -> Self {
+        match value {
+            Error::Api(err) => err,
+            Error::Request(err) => ApiError::new(ErrorCode::Unavailable, err.to_string()),
+            Error::InvalidKeySet(err) => ApiError::new(ErrorCode::Internal, err.to_string()),
+        }
+    }
+}
+
+/// Turns an error status into an [`Error::Api`], decoding the [`ApiError`] body when present.
+async fn ensure_success(response: Response) -> Result<Response> {
+    let status = response.status();
+
+    if status.is_success() {
+        return Ok(response);
+    }
+
+    let error = match response.json::<ApiError>().await {
+        Ok(error) => error,
+        Err(_) => ApiError::new(
+            ErrorCode::from_status(status.as_u16()),
+            status.canonical_reason().unwrap_or("Request failed"),
+        ),
+    };
+
+    Err(Error::Api(error))
+}
+
+async fn parse<T: DeserializeOwned>(response: Response) -> Result<T> {
+    let response = ensure_success(response).await?;
+
+    Ok(response.json::<T>().await?)
+}
+
 pub async fn authenticate(api_base_url: &str, credentials: &Credentials) -> Result<AuthResponse> {
     let response = CLIENT
         .request(Method::POST, format!("{api_base_url}/auth"))
@@ -23,19 +102,81 @@ pub async fn authenticate(api_base_url: &str, credentials: &Credentials) -> Resu
         .send()
         .await?;
 
-    let auth_response = response.json::<AuthResponse>().await?;
+    let auth_response = parse::<AuthResponse>(response).await?;
 
     Ok(auth_response)
 }
 
+pub async fn get_jwks(api_base_url: &str) -> Result<JwkSet> {
+    let response = CLIENT
+        .request(Method::GET, format!("{api_base_url}/.well-known/jwks.json"))
+        .send()
+        .await?;
+
+    let response = ensure_success(response).await?;
+    let jwks = JwkSet::from_bytes(response.bytes().await?)?;
+
+    Ok(jwks)
+}
+
+pub async fn refresh(api_base_url: &str, refresh_token: &str) -> Result<AuthResponse> {
+    let response = CLIENT
+        .request(Method::POST, format!("{api_base_url}/auth/refresh"))
+        .json(&RefreshRequest {
+            refresh_token: refresh_token.to_string(),
+        })
+        .send()
+        .await?;
+
+    let auth_response = parse::<AuthResponse>(response).await?;
+
+    Ok(auth_response)
+}
+
+pub async fn logout(api_base_url: &str, token: &str) -> Result<()> {
+    let response = CLIENT
+        .request(Method::POST, format!("{api_base_url}/auth/logout"))
+        .bearer_auth(token)
+        .send()
+        .await?;
+
+    ensure_success(response).await?;
+
+    Ok(())
+}
+
+pub async fn list_sessions(api_base_url: &str, token: &str) -> Result<Vec<Session>> {
+    let response = CLIENT
+        .request(Method::GET, format!("{api_base_url}/auth/sessions"))
+        .bearer_auth(token)
+        .send()
+        .await?;
+
+    let sessions = parse::<Vec<Session>>(response).await?;
+
+    Ok(sessions)
+}
+
+pub async fn revoke_session(api_base_url: &str, token: &str, id: &Uuid) -> Result<()> {
+    let response = CLIENT
+        .request(Method::DELETE, format!("{api_base_url}/auth/sessions/{id}"))
+        .bearer_auth(token)
+        .send()
+        .await?;
+
+    ensure_success(response).await?;
+
+    Ok(())
+}
+
 pub async fn get_profile(api_base_url: &str, token: &str) -> Result<User> {
     let response = CLIENT
         .request(Method::GET, format!("{api_base_url}/auth"))
-        .header("Authorization", format!("Bearer: {}", token))
+        .bearer_auth(token)
         .send()
         .await?;
 
-    let user = response.json::<User>().await.unwrap();
+    let user = parse::<User>(response).await?;
 
     Ok(user)
 }
@@ -46,7 +187,7 @@ pub async fn get_user(api_base_url: &str, id: &Uuid) -> Result<User> {
         .send()
         .await?;
 
-    let user = response.json::<User>().await.unwrap();
+    let user = parse::<User>(response).await?;
 
     Ok(user)
 }
@@ -58,41 +199,134 @@ pub async fn register_user(api_base_url: &str, new_user: NewUser) -> Result<Auth
         .send()
         .await?;
 
-    let user = response.json::<AuthResponse>().await?;
+    let user = parse::<AuthResponse>(response).await?;
 
     Ok(user)
 }
 
-pub async fn list_servers(api_base_url: &str) -> Result<Vec<Server>> {
+pub async fn list_servers(api_base_url: &str, query: &ServerQuery) -> Result<ServerPage> {
     let response = CLIENT
         .request(Method::GET, format!("{api_base_url}/servers"))
+        .query(query)
         .send()
         .await?;
 
-    let servers = response.json::<Vec<Server>>().await.unwrap();
+    let page = parse::<ServerPage>(response).await?;
 
-    Ok(servers)
+    Ok(page)
 }
 
-pub async fn register_server(api_base_url: &str, new_server: &RegisterServer) -> Result<Server> {
+pub async fn register_server(
+    api_base_url: &str,
+    token: &str,
+    new_server: &RegisterServer,
+) -> Result<RegisteredServer> {
     let response = CLIENT
         .request(Method::POST, format!("{api_base_url}/servers"))
+        .bearer_auth(token)
         .json(new_server)
         .send()
         .await?;
 
-    let server = response.json::<Server>().await?;
+    let server = parse::<RegisteredServer>(response).await?;
+
+    Ok(server)
+}
+
+pub async fn update_server(
+    api_base_url: &str,
+    api_key: &str,
+    id: &Uuid,
+    server: &UpdateServer,
+) -> Result<Server> {
+    let response = CLIENT
+        .request(Method::PUT, format!("{api_base_url}/servers/{id}"))
+        .bearer_auth(api_key)
+        .json(server)
+        .send()
+        .await?;
+
+    let server = parse::<Server>(response).await?;
 
     Ok(server)
 }
 
-pub async fn ping_server(api_base_url: &str, id: &Uuid) -> Result<Server> {
+pub async fn deregister_server(api_base_url: &str, api_key: &str, id: &Uuid) -> Result<()> {
+    let response = CLIENT
+        .request(Method::DELETE, format!("{api_base_url}/servers/{id}"))
+        .bearer_auth(api_key)
+        .send()
+        .await?;
+
+    ensure_success(response).await?;
+
+    Ok(())
+}
+
+pub async fn rotate_server_key(
+    api_base_url: &str,
+    token: &str,
+    id: &Uuid,
+) -> Result<RegisteredServer> {
+    let response = CLIENT
+        .request(Method::POST, format!("{api_base_url}/servers/{id}/key"))
+        .bearer_auth(token)
+        .send()
+        .await?;
+
+    let server = parse::<RegisteredServer>(response).await?;
+
+    Ok(server)
+}
+
+pub async fn ping_server(
+    api_base_url: &str,
+    api_key: &str,
+    id: &Uuid,
+    status: &ServerStatus,
+) -> Result<Server> {
     let response = CLIENT
         .request(Method::POST, format!("{api_base_url}/servers/{id}/ping"))
+        .bearer_auth(api_key)
+        .json(status)
         .send()
         .await?;
 
-    let server = response.json::<Server>().await?;
+    let server = parse::<Server>(response).await?;
 
     Ok(server)
 }
+
+/// Returns the active bans of the given players.
+pub async fn check_bans(
+    api_base_url: &str,
+    api_key: &str,
+    id: &Uuid,
+    user_ids: Vec<Uuid>,
+) -> Result<Vec<UserBan>> {
+    let response = CLIENT
+        .request(
+            Method::POST,
+            format!("{api_base_url}/servers/{id}/bans/check"),
+        )
+        .bearer_auth(api_key)
+        .json(&BanCheck { user_ids })
+        .send()
+        .await?;
+
+    let bans = parse::<Vec<UserBan>>(response).await?;
+
+    Ok(bans)
+}
+
+pub async fn issue_join_ticket(api_base_url: &str, token: &str, id: &Uuid) -> Result<JoinTicket> {
+    let response = CLIENT
+        .request(Method::POST, format!("{api_base_url}/servers/{id}/tickets"))
+        .bearer_auth(token)
+        .send()
+        .await?;
+
+    let ticket = parse::<JoinTicket>(response).await?;
+
+    Ok(ticket)
+}
@@ -1,6 +1,7 @@
 use bevy::ecs::component::Component;
 use bevy::math::Vec3;
 use bevy_quinnet::shared::ClientId;
+use models::api::users::Role;
 use uuid::Uuid;
 
 #[derive(Component)]
@@ -11,3 +12,7 @@ pub struct Player {
 
 #[derive(Default, Component)]
 pub struct PlayerPosition(pub Vec3);
+
+/// The global role of the player, from its join ticket.
+#[derive(Default, Component)]
+pub struct PlayerRole(pub Role);
@@ -2,3 +2,4 @@ pub mod api_client;
 pub mod components;
 pub mod models;
 pub mod plugins;
+pub mod tokens;
@@ -1,21 +1,121 @@
 use bevy::math::Vec3;
 use bevy_quinnet::shared::ClientId;
 use serde::{Deserialize, Serialize};
+use std::{
+    fmt::{self, Display, Formatter},
+    time::Duration,
+};
+use time::OffsetDateTime;
 use uuid::Uuid;
 
-use crate::components::movement::MoveModifier;
+use crate::components::movement::Movement;
+
+/// How often clients let the server know they are still there.
+pub const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(5);
 
 #[derive(Debug, Deserialize, Serialize)]
 pub enum ClientMessage {
-    Join { user_id: Uuid },
+    Join {
+        ticket: String,
+    },
     Disconnect,
-    ChatMessage { message: String },
-    UpdatePosition { position: Vec3 },
-    SendModifier(MoveModifier),
+    Heartbeat,
+    ChatMessage {
+        channel: ChatChannel,
+        message: String,
+    },
+    /// Asks for the chat sent before the oldest message the client has
+    RequestChatHistory {
+        /// The sequence number of that message
+        before: u64,
+    },
+    /// What the player does on the next tick, sent every tick
+    Input(PlayerInput),
+}
+
+/// The movement of a player for a single tick.
+#[derive(Debug, Clone, Copy, Deserialize, Serialize)]
+pub struct PlayerInput {
+    /// Counts up with every input, so the server can tell which it has applied
+    pub sequence: u32,
+    pub movement: Movement,
+}
+
+/// Who gets to see a chat message.
+#[derive(PartialEq, Eq, Hash, Debug, Clone, Copy, Deserialize, Serialize)]
+pub enum ChatChannel {
+    /// Everyone on the server
+    Global,
+    /// The players on the same team as the sender
+    Team,
+    /// Only the sender and this user
+    Whisper(Uuid),
+    /// Messages from the server itself, players can't send to it
+    System,
+}
+
+impl Display for ChatChannel {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            ChatChannel::Global => write!(f, "global"),
+            ChatChannel::Team => write!(f, "team"),
+            ChatChannel::Whisper(_) => write!(f, "whisper"),
+            ChatChannel::System => write!(f, "system"),
+        }
+    }
+}
+
+/// A chat message sent before the client asked for it, by user as the sender may have left.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ChatHistoryEntry {
+    /// Where the message is in the chat log, to ask for the messages before it
+    pub seq: u64,
+    /// Absent for messages from the server itself
+    pub user_id: Option<Uuid>,
+    pub channel: ChatChannel,
+    pub message: String,
+    pub sent_at: OffsetDateTime,
+}
+
+/// Why the server closed the connection of a client.
+#[derive(PartialEq, Debug, Clone, Deserialize, Serialize)]
+pub enum KickReason {
+    Kicked { reason: Option<String> },
+    Banned { reason: Option<String> },
+    NotWhitelisted,
+    ServerFull,
+    ServerShutdown,
+    Idle,
+}
+
+impl Display for KickReason {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            KickReason::Kicked { reason: None } => write!(f, "You were kicked from the server"),
+            KickReason::Kicked {
+                reason: Some(reason),
+            } => write!(f, "You were kicked from the server: {reason}"),
+            KickReason::Banned { reason: None } => write!(f, "You are banned from this server"),
+            KickReason::Banned {
+                reason: Some(reason),
+            } => write!(f, "You are banned from this server: {reason}"),
+            KickReason::NotWhitelisted => write!(f, "You are not whitelisted on this server"),
+            KickReason::ServerFull => write!(f, "The server is full"),
+            KickReason::ServerShutdown => write!(f, "The server is shutting down"),
+            KickReason::Idle => write!(f, "You were disconnected for being idle"),
+        }
+    }
 }
 
 #[derive(Debug, Deserialize, Serialize)]
 pub enum ServerMessage {
+    JoinRejected {
+        reason: String,
+    },
+    /// Sent right before the server closes the connection
+    Kicked {
+        reason: KickReason,
+    },
     ClientConnected {
         client_id: ClientId,
         user_id: Uuid,
@@ -24,15 +124,36 @@ pub enum ServerMessage {
         client_id: ClientId,
     },
     ChatMessage {
-        client_id: ClientId,
+        /// Absent for messages from the server itself
+        client_id: Option<ClientId>,
+        channel: ChatChannel,
         message: String,
+        sent_at: OffsetDateTime,
+    },
+    /// Earlier chat, oldest first, sent on joining and when asked for
+    ChatHistory {
+        messages: Vec<ChatHistoryEntry>,
+        /// Whether there are older messages to ask for
+        more: bool,
     },
     UpdatePosition {
         client_id: ClientId,
         position: Vec3,
+        /// The sequence of the last input of the player applied to `position`
+        input_sequence: u32,
+        /// The tick of the server the position is from
+        tick: u64,
     },
-    SendModifier {
-        client_id: ClientId,
-        modifier: MoveModifier,
-    },
+}
+
+impl ServerMessage {
+    /// A chat message from the server itself.
+    pub fn system(message: impl Into<String>) -> Self {
+        ServerMessage::ChatMessage {
+            client_id: None,
+            channel: ChatChannel::System,
+            message: message.into(),
+            sent_at: OffsetDateTime::now_utc(),
+        }
+    }
 }
@@ -2,35 +2,76 @@ use crate::components::{
     movement::Movement,
     player::{Player, PlayerPosition},
 };
-use bevy::app::{App, Plugin, Update};
+use bevy::{
+    app::{App, FixedUpdate, Plugin},
+    math::Vec3,
+    time::{Fixed, Time},
+};
 use bevy_ecs::prelude::*;
 
+/// How many times a second movement is simulated, the same on the server and the clients so
+/// inputs mean the same everywhere.
+pub const TICK_RATE: f64 = 60.0;
+
+/// Moves players and advances the [`Tick`], schedule what decides their [`Movement`] before it.
+#[derive(SystemSet, PartialEq, Eq, Hash, Debug, Clone)]
+pub struct MovementSet;
+
+/// How many ticks were simulated so far.
+#[derive(Default, Resource)]
+pub struct Tick(pub u64);
+
+impl Tick {
+    /// When the tick happened, in seconds since the first.
+    pub fn seconds(tick: u64) -> f64 {
+        tick as f64 / TICK_RATE
+    }
+}
+
 pub struct MovementPlugin;
 
 impl Plugin for MovementPlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(Update, handle_movement);
+        app.insert_resource(Time::<Fixed>::from_hz(TICK_RATE))
+            .init_resource::<Tick>()
+            .add_systems(
+                FixedUpdate,
+                (handle_movement, advance_tick).chain().in_set(MovementSet),
+            );
     }
 }
 
 static SPEED: f32 = 0.1;
 
-fn handle_movement(mut players: Query<(&mut PlayerPosition, &Movement), With<Player>>) {
-    for (mut player_position, movement) in players.iter_mut() {
-        if movement.forward {
-            player_position.0.x += SPEED;
-        }
+/// Where a player at `position` ends up after a tick of `movement`.
+pub fn step(position: Vec3, movement: &Movement) -> Vec3 {
+    let mut position = position;
 
-        if movement.backward {
-            player_position.0.x -= SPEED;
-        }
+    if movement.forward {
+        position.x += SPEED;
+    }
 
-        if movement.left {
-            player_position.0.z -= SPEED;
-        }
+    if movement.backward {
+        position.x -= SPEED;
+    }
 
-        if movement.right {
-            player_position.0.z += SPEED;
-        }
+    if movement.left {
+        position.z -= SPEED;
     }
+
+    if movement.right {
+        position.z += SPEED;
+    }
+
+    position
+}
+
+fn handle_movement(mut players: Query<(&mut PlayerPosition, &Movement), With<Player>>) {
+    for (mut player_position, movement) in players.iter_mut() {
+        player_position.0 = step(player_position.0, movement);
+    }
+}
+
+fn advance_tick(mut tick: ResMut<Tick>) {
+    tick.0 += 1;
 }
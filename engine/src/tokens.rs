@@ -0,0 +1,180 @@
+use crate::api_client::get_jwks;
+use anyhow::{anyhow, Result};
+use josekit::{
+    jwk::JwkSet,
+    jws::{JwsVerifier, ES256, RS256},
+    jwt::{self, JwtPayload, JwtPayloadValidator},
+    Value,
+};
+use models::api::users::Role;
+use std::{
+    collections::HashMap,
+    str::FromStr,
+    sync::{Arc, RwLock},
+    time::Duration,
+};
+use tokio::{sync::Notify, time::timeout};
+use uuid::Uuid;
+
+/// How often the key set is fetched when no unknown keys are seen.
+const REFRESH_INTERVAL: Duration = Duration::from_secs(10 * 60);
+
+/// The minimum time between two fetches, so unknown key ids can't be used to flood the api.
+const MIN_REFRESH_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Verifies tokens issued by the api against the public keys it publishes at
+/// `/.well-known/jwks.json`.
+///
+/// The key set is cached, verification itself never waits on the network. Tokens signed with
+/// an unknown key are rejected and wake up [`TokenVerifier::run`] to fetch the key set again.
+#[derive(Clone)]
+pub struct TokenVerifier {
+    api_base_url: String,
+    issuer: String,
+    keys: Arc<RwLock<HashMap<String, Box<dyn JwsVerifier>>>>,
+    refresh_requested: Arc<Notify>,
+}
+
+/// The verified claims of a join ticket.
+#[derive(Clone, Debug)]
+pub struct JoinClaims {
+    pub user_id: Uuid,
+    pub username: String,
+    /// The global role of the user, moderators and admins get extra powers on game servers
+    pub role: Role,
+}
+
+impl TokenVerifier {
+    pub fn new(api_base_url: String, issuer: String) -> Self {
+        Self {
+            api_base_url,
+            issuer,
+            keys: Arc::new(RwLock::new(HashMap::new())),
+            refresh_requested: Arc::new(Notify::new()),
+        }
+    }
+
+    /// Fetches the key set from the api and replaces the cached keys.
+    pub async fn refresh(&self) -> Result<()> {
+        let jwks = get_jwks(&self.api_base_url).await?;
+        let keys = verifiers_from_jwks(&jwks)?;
+
+        *self.keys.write().unwrap() = keys;
+
+        Ok(())
+    }
+
+    /// Keeps the cached key set up to date, meant to be spawned as a background task.
+    pub async fn run(self) {
+        loop {
+            if let Err(err) = self.refresh().await {
+                tracing::warn!("Failed to fetch the api key set: {err}");
+            }
+
+            tokio::time::sleep(MIN_REFRESH_INTERVAL).await;
+            let _ = timeout(
+                REFRESH_INTERVAL - MIN_REFRESH_INTERVAL,
+                self.refresh_requested.notified(),
+            )
+            .await;
+        }
+    }
+
+    /// Verifies a ticket handed out by the api for joining the server with the given id.
+    pub fn verify_join_ticket(&self, ticket: &str, server_id: &Uuid) -> Result<JoinClaims> {
+        let payload = self.verify(ticket, |validator| {
+            validator.set_audience(server_id.to_string());
+            validator.set_claim("typ", Value::String(String::from("join")));
+        })?;
+
+        if payload.audience().is_none() {
+            return Err(anyhow!("join ticket has no audience"));
+        }
+
+        let subject = payload
+            .subject()
+            .ok_or(anyhow!("join ticket has no subject"))?;
+        let username = payload
+            .claim("name")
+            .and_then(Value::as_str)
+            .ok_or(anyhow!("join ticket has no name"))?;
+        let role = payload
+            .claim("role")
+            .and_then(Value::as_str)
+            .and_then(|role| Role::from_str(role).ok())
+            .unwrap_or_default();
+
+        Ok(JoinClaims {
+            user_id: Uuid::from_str(subject)?,
+            username: username.to_string(),
+            role,
+        })
+    }
+
+    /// Verifies an access token and returns the id of its user.
+    ///
+    /// This only checks the signature and claims, a session revoked since the token was issued
+    /// is only noticed by the api.
+    pub fn verify_access_token(&self, token: &str) -> Result<Uuid> {
+        let payload = self.verify(token, |_| {})?;
+
+        if payload.claim("sid").is_none() {
+            return Err(anyhow!("token is not an access token"));
+        }
+
+        let subject = payload.subject().ok_or(anyhow!("token has no subject"))?;
+
+        Ok(Uuid::from_str(subject)?)
+    }
+
+    fn verify<F>(&self, token: &str, configure: F) -> Result<JwtPayload>
+    where
+        F: FnOnce(&mut JwtPayloadValidator),
+    {
+        let header = jwt::decode_header(token)?;
+        let kid = header
+            .claim("kid")
+            .and_then(Value::as_str)
+            .ok_or(anyhow!("token has no key id"))?;
+
+        let keys = self.keys.read().unwrap();
+        let verifier = match keys.get(kid) {
+            Some(verifier) => verifier,
+            None => {
+                self.refresh_requested.notify_one();
+                return Err(anyhow!("token is signed with unknown key {kid}"));
+            }
+        };
+
+        let (payload, _header) = jwt::decode_with_verifier(token, &**verifier)?;
+
+        if payload.expires_at().is_none() || payload.issued_at().is_none() {
+            return Err(anyhow!("token is missing exp or iat"));
+        }
+
+        let mut validator = JwtPayloadValidator::new();
+        validator.set_issuer(&self.issuer);
+        configure(&mut validator);
+        validator.validate(&payload)?;
+
+        Ok(payload)
+    }
+}
+
+fn verifiers_from_jwks(jwks: &JwkSet) -> Result<HashMap<String, Box<dyn JwsVerifier>>> {
+    let mut keys = HashMap::new();
+
+    for jwk in jwks.keys() {
+        let kid = jwk.key_id().ok_or(anyhow!("key without key id"))?;
+
+        let verifier: Box<dyn JwsVerifier> = match jwk.algorithm() {
+            Some("ES256") => Box::new(ES256.verifier_from_jwk(jwk)?),
+            Some("RS256") => Box::new(RS256.verifier_from_jwk(jwk)?),
+            algorithm => return Err(anyhow!("unsupported key algorithm {algorithm:?}")),
+        };
+
+        keys.insert(kid.to_string(), verifier);
+    }
+
+    Ok(keys)
+}
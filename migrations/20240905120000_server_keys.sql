@@ -0,0 +1,9 @@
+-- Registrations without an owner can't be claimed by anyone, drop them.
+DELETE FROM servers;
+
+ALTER TABLE servers
+    ADD COLUMN owner_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    ADD COLUMN api_key_hash VARCHAR(255) NOT NULL,
+    ADD COLUMN allow_any_addr BOOLEAN NOT NULL DEFAULT false;
+
+CREATE INDEX servers_owner_id_idx ON servers (owner_id);
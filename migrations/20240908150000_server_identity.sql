@@ -0,0 +1,9 @@
+-- Keep only the most recent registration of every owner, addr and port.
+DELETE FROM servers a
+    USING servers b
+    WHERE a.owner_id = b.owner_id
+        AND a.addr = b.addr
+        AND a.port = b.port
+        AND (a.last_ping, a.id) < (b.last_ping, b.id);
+
+CREATE UNIQUE INDEX servers_owner_id_addr_port_idx ON servers (owner_id, addr, port);
@@ -0,0 +1,7 @@
+ALTER TABLE servers
+    ADD COLUMN player_count INTEGER NOT NULL DEFAULT 0,
+    ADD COLUMN max_players INTEGER NOT NULL DEFAULT 0,
+    ADD COLUMN game_mode VARCHAR(64),
+    ADD COLUMN map VARCHAR(255),
+    ADD COLUMN version VARCHAR(64),
+    ADD COLUMN tags TEXT[] NOT NULL DEFAULT '{}';
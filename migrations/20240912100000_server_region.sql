@@ -0,0 +1,3 @@
+ALTER TABLE servers ADD COLUMN region VARCHAR(32);
+
+CREATE INDEX servers_last_ping_idx ON servers (last_ping);
@@ -1,3 +1,5 @@
 pub mod auth;
+pub mod error;
 pub mod servers;
+pub mod user_bans;
 pub mod users;
@@ -1,8 +1,6 @@
-use josekit::{
-    jws::JwsHeader,
-    jwt::{self, JwtPayload},
-};
 use serde::{Deserialize, Serialize};
+use time::OffsetDateTime;
+use uuid::Uuid;
 
 #[derive(Serialize, Deserialize)]
 pub struct Credentials {
@@ -10,21 +8,42 @@ pub struct Credentials {
     pub password: String,
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Clone, Serialize, Deserialize)]
 pub struct AuthResponse {
     pub token: String,
+    #[serde(with = "time::serde::rfc3339")]
+    pub expires_at: OffsetDateTime,
+    pub refresh_token: String,
 }
 
-impl AuthResponse {
-    pub fn from_user(value: crate::data::users::User) -> Self {
-        let mut header = JwsHeader::new();
-        header.set_token_type("JWT");
-
-        let mut payload = JwtPayload::new();
-        payload.set_subject(value.id.to_string().as_str());
+#[derive(Serialize, Deserialize)]
+pub struct RefreshRequest {
+    pub refresh_token: String,
+}
 
-        let token = jwt::encode_unsecured(&payload, &header).unwrap();
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Session {
+    pub id: Uuid,
+    pub user_agent: Option<String>,
+    #[serde(with = "time::serde::rfc3339")]
+    pub created_at: OffsetDateTime,
+    #[serde(with = "time::serde::rfc3339")]
+    pub last_used_at: OffsetDateTime,
+    #[serde(with = "time::serde::rfc3339")]
+    pub expires_at: OffsetDateTime,
+    /// Whether this is the session the request was made with
+    pub current: bool,
+}
 
-        Self { token }
+impl Session {
+    pub fn from_data(value: crate::data::sessions::Session, current_id: Uuid) -> Self {
+        Self {
+            id: value.id,
+            user_agent: value.user_agent,
+            created_at: value.created_at,
+            last_used_at: value.last_used_at,
+            expires_at: value.expires_at,
+            current: value.id == current_id,
+        }
     }
 }
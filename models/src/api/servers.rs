@@ -1,3 +1,4 @@
+use super::error::{ApiError, FieldError};
 use serde::{Deserialize, Serialize};
 use std::net::IpAddr;
 use time::OffsetDateTime;
@@ -11,16 +12,21 @@ pub struct Server {
     pub name: String,
     #[serde(with = "time::serde::rfc3339")]
     pub last_ping: OffsetDateTime,
+    pub owner_id: Uuid,
+    #[serde(flatten)]
+    pub status: ServerStatus,
 }
 
 impl Server {
-    pub fn new(addr: IpAddr, port: u16, name: String) -> Self {
+    pub fn new(addr: IpAddr, port: u16, name: String, owner_id: Uuid) -> Self {
         Self {
             id: Uuid::new_v4(),
             name,
             addr,
             port,
             last_ping: OffsetDateTime::now_utc(),
+            owner_id,
+            status: ServerStatus::default(),
         }
     }
 
@@ -39,6 +45,77 @@ impl From<crate::data::servers::Server> for Server {
             port,
             name: value.name,
             last_ping: value.last_ping,
+            owner_id: value.owner_id,
+            status: ServerStatus {
+                player_count: value.player_count.try_into().unwrap_or_default(),
+                max_players: value.max_players.try_into().unwrap_or_default(),
+                game_mode: value.game_mode,
+                map: value.map,
+                version: value.version,
+                region: value.region,
+                tags: value.tags,
+            },
+        }
+    }
+}
+
+/// What a server is running and how full it is, reported with every ping.
+#[derive(PartialEq, Debug, Clone, Default, Deserialize, Serialize)]
+pub struct ServerStatus {
+    pub player_count: u32,
+    pub max_players: u32,
+    pub game_mode: Option<String>,
+    pub map: Option<String>,
+    pub version: Option<String>,
+    pub region: Option<String>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+}
+
+impl ServerStatus {
+    pub const MAX_TAGS: usize = 16;
+    pub const MAX_TAG_LENGTH: usize = 32;
+
+    pub fn validate(&self) -> Result<(), ApiError> {
+        let mut fields = Vec::new();
+
+        if self.player_count > self.max_players {
+            fields.push(FieldError::new(
+                "player_count",
+                "Player count can not exceed max players",
+            ));
+        }
+
+        if self.max_players > i32::MAX as u32 {
+            fields.push(FieldError::new("max_players", "Max players is too large"));
+        }
+
+        for (field, value, max) in [
+            ("game_mode", &self.game_mode, 64),
+            ("map", &self.map, 255),
+            ("version", &self.version, 64),
+            ("region", &self.region, 32),
+        ] {
+            if value.as_ref().is_some_and(|value| value.len() > max) {
+                fields.push(FieldError::new(field, "Value is too long"));
+            }
+        }
+
+        if self.tags.len() > Self::MAX_TAGS {
+            fields.push(FieldError::new("tags", "Too many tags"));
+        }
+
+        if self
+            .tags
+            .iter()
+            .any(|tag| tag.is_empty() || tag.len() > Self::MAX_TAG_LENGTH)
+        {
+            fields.push(FieldError::new("tags", "Tags must be 1 to 32 characters"));
+        }
+
+        match fields.is_empty() {
+            true => Ok(()),
+            false => Err(ApiError::validation(fields)),
         }
     }
 }
@@ -48,4 +125,83 @@ pub struct RegisterServer {
     pub addr: IpAddr,
     pub port: u16,
     pub name: String,
+    /// Accept calls from any address, for servers behind a NAT
+    #[serde(default)]
+    pub allow_any_addr: bool,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct UpdateServer {
+    pub addr: IpAddr,
+    pub port: u16,
+    pub name: String,
+}
+
+/// A server along with its api key, which is only ever handed out once.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct RegisteredServer {
+    pub server: Server,
+    pub api_key: String,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct JoinTicket {
+    pub ticket: String,
+    #[serde(with = "time::serde::rfc3339")]
+    pub expires_at: OffsetDateTime,
+}
+
+#[derive(PartialEq, Eq, Debug, Clone, Copy, Default, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ServerSort {
+    /// Most recently pinged first
+    #[default]
+    LastPing,
+    /// Most players first
+    Players,
+    /// Alphabetically by name
+    Name,
+}
+
+/// The query parameters of `GET /servers`.
+#[derive(PartialEq, Debug, Clone, Default, Deserialize, Serialize)]
+pub struct ServerQuery {
+    /// Only servers whose name contains this, case insensitive
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub search: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tag: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub game_mode: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub region: Option<String>,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub not_full: bool,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub has_players: bool,
+    #[serde(default)]
+    pub sort: ServerSort,
+    /// The `next_cursor` of the previous page
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cursor: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub limit: Option<u32>,
+}
+
+impl ServerQuery {
+    pub const DEFAULT_LIMIT: u32 = 50;
+    pub const MAX_LIMIT: u32 = 100;
+
+    pub fn limit(&self) -> u32 {
+        self.limit
+            .unwrap_or(Self::DEFAULT_LIMIT)
+            .clamp(1, Self::MAX_LIMIT)
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ServerPage {
+    pub servers: Vec<Server>,
+    /// Pass as `cursor` to fetch the next page, absent on the last page
+    pub next_cursor: Option<String>,
 }
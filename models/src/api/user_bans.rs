@@ -0,0 +1,62 @@
+use serde::{Deserialize, Serialize};
+use time::{format_description::well_known::Rfc3339, OffsetDateTime};
+use uuid::Uuid;
+
+#[derive(PartialEq, Debug, Clone, Deserialize, Serialize)]
+pub struct UserBan {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub reason: Option<String>,
+    pub issued_by: Option<Uuid>,
+    #[serde(with = "time::serde::rfc3339")]
+    pub created_at: OffsetDateTime,
+    /// Permanent when absent
+    #[serde(default, with = "time::serde::rfc3339::option")]
+    pub expires_at: Option<OffsetDateTime>,
+    #[serde(default, with = "time::serde::rfc3339::option")]
+    pub lifted_at: Option<OffsetDateTime>,
+}
+
+impl UserBan {
+    /// What a banned user is told when they are turned away.
+    pub fn message(&self) -> String {
+        let mut message = match self.expires_at.and_then(|at| at.format(&Rfc3339).ok()) {
+            Some(expires_at) => format!("Account suspended until {expires_at}"),
+            None => String::from("Account banned"),
+        };
+
+        if let Some(reason) = &self.reason {
+            message.push_str(": ");
+            message.push_str(reason);
+        }
+
+        message
+    }
+}
+
+impl From<crate::data::user_bans::UserBan> for UserBan {
+    fn from(value: crate::data::user_bans::UserBan) -> Self {
+        Self {
+            id: value.id,
+            user_id: value.user_id,
+            reason: value.reason,
+            issued_by: value.issued_by,
+            created_at: value.created_at,
+            expires_at: value.expires_at,
+            lifted_at: value.lifted_at,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct NewUserBan {
+    pub reason: Option<String>,
+    #[serde(default, with = "time::serde::rfc3339::option")]
+    pub expires_at: Option<OffsetDateTime>,
+}
+
+/// The players a game server asks the active bans of.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct BanCheck {
+    pub user_ids: Vec<Uuid>,
+}
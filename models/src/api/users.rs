@@ -1,6 +1,45 @@
+use super::error::{ApiError, FieldError};
 use serde::{Deserialize, Serialize};
+use sqlx::Type;
+use std::str::FromStr;
 use uuid::Uuid;
 
+/// What a user is allowed to do, ordered from least to most privileged.
+#[derive(
+    PartialEq, Eq, PartialOrd, Ord, Hash, Debug, Clone, Copy, Default, Deserialize, Serialize, Type,
+)]
+#[serde(rename_all = "snake_case")]
+#[sqlx(type_name = "user_role", rename_all = "snake_case")]
+pub enum Role {
+    #[default]
+    Player,
+    Moderator,
+    Admin,
+}
+
+impl Role {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Role::Player => "player",
+            Role::Moderator => "moderator",
+            Role::Admin => "admin",
+        }
+    }
+}
+
+impl FromStr for Role {
+    type Err = ();
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "player" => Ok(Role::Player),
+            "moderator" => Ok(Role::Moderator),
+            "admin" => Ok(Role::Admin),
+            _ => Err(()),
+        }
+    }
+}
+
 #[derive(Deserialize, Serialize)]
 pub struct User {
     pub id: Uuid,
@@ -22,3 +61,67 @@ pub struct NewUser {
     pub email: String,
     pub password: String,
 }
+
+impl NewUser {
+    pub fn validate(&self) -> Result<(), ApiError> {
+        let mut fields = Vec::new();
+
+        if self.username.trim().is_empty() {
+            fields.push(FieldError::new("username", "Username can not be empty"));
+        }
+
+        if !self.email.contains('@') {
+            fields.push(FieldError::new("email", "Email address is invalid"));
+        }
+
+        if self.password.len() < 8 {
+            fields.push(FieldError::new(
+                "password",
+                "Password must be at least 8 characters",
+            ));
+        }
+
+        match fields.is_empty() {
+            true => Ok(()),
+            false => Err(ApiError::validation(fields)),
+        }
+    }
+}
+
+/// A user as seen by admins.
+#[derive(Deserialize, Serialize)]
+pub struct UserDetails {
+    pub id: Uuid,
+    pub username: String,
+    pub email: String,
+    pub role: Role,
+}
+
+impl From<crate::data::users::User> for UserDetails {
+    fn from(value: crate::data::users::User) -> Self {
+        Self {
+            id: value.id,
+            username: value.username,
+            email: value.email,
+            role: value.role,
+        }
+    }
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct UserQuery {
+    /// Only users whose username or email contains this, case insensitive
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub search: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub role: Option<Role>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub offset: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub limit: Option<u32>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct SetRole {
+    pub role: Role,
+}
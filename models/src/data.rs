@@ -1,2 +1,5 @@
 pub mod servers;
+pub mod sessions;
+pub mod tokens;
+pub mod user_bans;
 pub mod users;
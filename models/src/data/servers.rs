@@ -3,6 +3,8 @@ use std::net::IpAddr;
 use time::OffsetDateTime;
 use uuid::Uuid;
 
+use super::tokens::SecretToken;
+
 #[derive(FromRow)]
 pub struct Server {
     pub id: Uuid,
@@ -10,4 +12,28 @@ pub struct Server {
     pub addr: IpAddr,
     pub port: i32,
     pub last_ping: OffsetDateTime,
+    pub owner_id: Uuid,
+    pub api_key_hash: String,
+    pub allow_any_addr: bool,
+    pub player_count: i32,
+    pub max_players: i32,
+    pub game_mode: Option<String>,
+    pub map: Option<String>,
+    pub version: Option<String>,
+    pub region: Option<String>,
+    pub tags: Vec<String>,
 }
+
+impl Server {
+    pub fn verify_api_key(&self, key: &ServerKey) -> bool {
+        key.verify(self.id, &self.api_key_hash)
+    }
+
+    /// Whether a request from `addr` may act on behalf of this server.
+    pub fn accepts_addr(&self, addr: IpAddr) -> bool {
+        self.allow_any_addr || self.addr.to_canonical() == addr.to_canonical()
+    }
+}
+
+/// The api key of a server, its id followed by a secret.
+pub type ServerKey = SecretToken;
@@ -0,0 +1,26 @@
+use sqlx::prelude::FromRow;
+use time::OffsetDateTime;
+use uuid::Uuid;
+
+use super::tokens::SecretToken;
+
+#[derive(FromRow)]
+pub struct Session {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub refresh_token_hash: String,
+    pub user_agent: Option<String>,
+    pub created_at: OffsetDateTime,
+    pub last_used_at: OffsetDateTime,
+    pub expires_at: OffsetDateTime,
+    pub revoked_at: Option<OffsetDateTime>,
+}
+
+impl Session {
+    pub fn verify_refresh_token(&self, token: &RefreshToken) -> bool {
+        token.verify(self.id, &self.refresh_token_hash)
+    }
+}
+
+/// The refresh token of a session, its id followed by a secret.
+pub type RefreshToken = SecretToken;
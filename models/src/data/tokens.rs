@@ -0,0 +1,58 @@
+use sha2::{Digest, Sha256};
+use std::{
+    fmt::{self, Display, Formatter},
+    str::FromStr,
+};
+use uuid::Uuid;
+
+/// An opaque token of the form `<id>.<secret>`, where the id names what it belongs to.
+///
+/// Only a digest of the secret is stored. The secrets are random and long, so a fast hash is
+/// enough and keeps verifying cheap.
+pub struct SecretToken {
+    pub id: Uuid,
+    pub secret: String,
+}
+
+impl SecretToken {
+    pub fn generate(id: Uuid) -> Self {
+        let secret = format!("{}{}", Uuid::new_v4().simple(), Uuid::new_v4().simple());
+
+        Self { id, secret }
+    }
+
+    /// The SHA-256 digest of the secret, hex encoded.
+    pub fn digest(&self) -> String {
+        format!("{:x}", Sha256::digest(self.secret.as_bytes()))
+    }
+
+    /// Whether this is the token of `id` with the secret `digest` was made from.
+    pub fn verify(&self, id: Uuid, digest: &str) -> bool {
+        self.id == id && constant_time_eq(self.digest().as_bytes(), digest.as_bytes())
+    }
+}
+
+/// Compares without returning early, so the time taken doesn't leak how much matched.
+pub fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+impl FromStr for SecretToken {
+    type Err = ();
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let (id, secret) = value.split_once('.').ok_or(())?;
+        let id = Uuid::from_str(id).map_err(|_| ())?;
+
+        Ok(Self {
+            id,
+            secret: secret.to_string(),
+        })
+    }
+}
+
+impl Display for SecretToken {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}", self.id, self.secret)
+    }
+}
@@ -0,0 +1,15 @@
+use sqlx::prelude::FromRow;
+use time::OffsetDateTime;
+use uuid::Uuid;
+
+#[derive(FromRow)]
+pub struct UserBan {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub reason: Option<String>,
+    pub issued_by: Option<Uuid>,
+    pub created_at: OffsetDateTime,
+    pub expires_at: Option<OffsetDateTime>,
+    pub lifted_at: Option<OffsetDateTime>,
+    pub lifted_by: Option<Uuid>,
+}
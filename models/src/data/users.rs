@@ -1,3 +1,4 @@
+use crate::api::users::Role;
 use bcrypt::{hash, verify, DEFAULT_COST};
 use sqlx::prelude::FromRow;
 use uuid::Uuid;
@@ -8,6 +9,7 @@ pub struct User {
     pub username: String,
     pub email: String,
     pub password_hash: String,
+    pub role: Role,
 }
 
 impl User {
@@ -1,4 +1,5 @@
 use serde::{Deserialize, Serialize};
+use time::OffsetDateTime;
 use uuid::Uuid;
 
 use crate::api::servers::Server;
@@ -10,5 +11,157 @@ pub struct ServerInfoResponse {
 
 #[derive(PartialEq, Deserialize, Serialize)]
 pub struct PlayerResponse {
-    pub players: Vec<Uuid>,
+    pub players: Vec<PlayerDetails>,
+}
+
+#[derive(PartialEq, Debug, Clone, Deserialize, Serialize)]
+pub struct PlayerDetails {
+    pub user_id: Uuid,
+    pub username: String,
+    pub client_id: u64,
+    pub position: [f32; 3],
+    #[serde(with = "time::serde::rfc3339")]
+    pub connected_at: OffsetDateTime,
+    /// Absent when the connection is already gone
+    pub rtt_ms: Option<u64>,
+    pub bytes_sent: u64,
+    pub bytes_received: u64,
+    pub movement: MovementState,
+}
+
+/// Something that happened on the server, streamed to management clients.
+#[derive(PartialEq, Debug, Clone, Deserialize, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ServerEvent {
+    PlayerJoined {
+        player: PlayerDetails,
+    },
+    PlayerLeft {
+        user_id: Uuid,
+    },
+    PlayerKicked {
+        user_id: Uuid,
+        reason: String,
+    },
+    ChatMessage {
+        user_id: Uuid,
+        username: String,
+        /// `global`, `team` or `whisper`
+        channel: String,
+        message: String,
+    },
+    /// Sent periodically with the position of every player
+    Positions {
+        players: Vec<PositionSnapshot>,
+    },
+}
+
+#[derive(PartialEq, Debug, Clone, Deserialize, Serialize)]
+pub struct PositionSnapshot {
+    pub user_id: Uuid,
+    pub position: [f32; 3],
+}
+
+/// Which directions the player is currently moving in.
+#[derive(PartialEq, Debug, Clone, Default, Deserialize, Serialize)]
+pub struct MovementState {
+    pub forward: bool,
+    pub backward: bool,
+    pub left: bool,
+    pub right: bool,
+}
+
+#[derive(PartialEq, Debug, Clone, Deserialize, Serialize)]
+pub struct Ban {
+    pub user_id: Uuid,
+    pub reason: Option<String>,
+    #[serde(with = "time::serde::rfc3339")]
+    pub created_at: OffsetDateTime,
+    /// Permanent when absent
+    #[serde(default, with = "time::serde::rfc3339::option")]
+    pub expires_at: Option<OffsetDateTime>,
+}
+
+impl Ban {
+    pub fn is_active(&self, now: OffsetDateTime) -> bool {
+        self.expires_at.is_none_or(|expires_at| expires_at > now)
+    }
+}
+
+#[derive(PartialEq, Debug, Clone, Deserialize, Serialize)]
+pub struct NewBan {
+    pub user_id: Uuid,
+    pub reason: Option<String>,
+    #[serde(default, with = "time::serde::rfc3339::option")]
+    pub expires_at: Option<OffsetDateTime>,
+}
+
+#[derive(PartialEq, Debug, Clone, Default, Deserialize, Serialize)]
+pub struct Whitelist {
+    /// Only whitelisted users can join while enabled
+    pub enabled: bool,
+    pub user_ids: Vec<Uuid>,
+}
+
+#[derive(PartialEq, Debug, Clone, Deserialize, Serialize)]
+pub struct SetWhitelistEnabled {
+    pub enabled: bool,
+}
+
+#[derive(PartialEq, Debug, Clone, Deserialize, Serialize)]
+pub struct Broadcast {
+    pub message: String,
+}
+
+#[derive(PartialEq, Debug, Clone, Deserialize, Serialize)]
+pub struct Teleport {
+    pub position: [f32; 3],
+}
+
+#[derive(PartialEq, Debug, Clone, Deserialize, Serialize)]
+pub struct Mute {
+    /// Muted until unmuted or disconnected when absent
+    pub duration_secs: Option<u64>,
+}
+
+#[derive(PartialEq, Debug, Clone, Deserialize, Serialize)]
+pub struct RenameServer {
+    pub name: String,
+}
+
+/// What chat moderation did since the server started.
+#[derive(PartialEq, Debug, Clone, Default, Deserialize, Serialize)]
+pub struct ChatStats {
+    /// Messages delivered, including masked ones
+    pub messages: u64,
+    pub rate_limited: u64,
+    pub too_long: u64,
+    pub blocked: u64,
+    pub masked: u64,
+    pub auto_mutes: u64,
+}
+
+/// A chat message as kept in the chat log of the server.
+#[derive(PartialEq, Debug, Clone, Deserialize, Serialize)]
+pub struct ChatLogEntry {
+    /// Where the message is in the chat log, to page from
+    pub seq: u64,
+    /// Absent for messages from the server itself
+    pub user_id: Option<Uuid>,
+    pub username: Option<String>,
+    pub channel: String,
+    /// The team of the sender of a team message
+    pub team: Option<String>,
+    /// Who a whisper was sent to
+    pub recipient: Option<Uuid>,
+    pub message: String,
+    #[serde(with = "time::serde::rfc3339")]
+    pub sent_at: OffsetDateTime,
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct ChatLogQuery {
+    /// Only messages before the one with this sequence number, the most recent when absent
+    pub before: Option<u64>,
+    pub limit: Option<usize>,
 }
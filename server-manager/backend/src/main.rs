@@ -1,29 +1,158 @@
 // Prevents additional console window on Windows in release, DO NOT REMOVE!!
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
-use models::server::api::PlayerResponse;
+use models::server::api::{ChatLogEntry, PlayerDetails, PlayerResponse};
 use once_cell::sync::Lazy;
 use server::server_api_client::ServerApiClient;
+use std::{env, time::Duration};
+use tauri::{AppHandle, Manager};
 use uuid::Uuid;
 
-// TODO: Make configurable
-static CLIENT: Lazy<ServerApiClient> = Lazy::new(|| ServerApiClient::new("http://localhost:3001"));
+/// The client of the game server, or why there is none.
+static CLIENT: Lazy<Result<ServerApiClient, String>> = Lazy::new(|| {
+    let base_url =
+        env::var("CQ_SERVER_URL").unwrap_or_else(|_| String::from("http://localhost:3001"));
+    let admin_token =
+        env::var("CQ_ADMIN_TOKEN").map_err(|_| String::from("CQ_ADMIN_TOKEN must be set"))?;
+
+    Ok(ServerApiClient::new(base_url, admin_token))
+});
+
+fn client() -> Result<&'static ServerApiClient, String> {
+    CLIENT.as_ref().map_err(Clone::clone)
+}
 
 #[tauri::command]
-async fn get_players() -> Result<PlayerResponse, ()> {
-    let response = CLIENT.get_players().await.unwrap();
+async fn get_players() -> Result<PlayerResponse, String> {
+    let response = client()?
+        .get_players()
+        .await
+        .map_err(|err| err.to_string())?;
     Ok(response)
 }
 
 #[tauri::command]
-async fn kick_player(id: Uuid) -> Result<(), ()> {
-    CLIENT.kick_player(id).await.unwrap();
+async fn get_player(id: Uuid) -> Result<PlayerDetails, String> {
+    let response = client()?
+        .get_player(id)
+        .await
+        .map_err(|err| err.to_string())?;
+    Ok(response)
+}
+
+#[tauri::command]
+async fn kick_player(id: Uuid, reason: Option<String>) -> Result<(), String> {
+    client()?
+        .kick_player(id, reason.as_deref())
+        .await
+        .map_err(|err| err.to_string())?;
+    Ok(())
+}
+
+#[tauri::command]
+async fn broadcast(message: String) -> Result<(), String> {
+    client()?
+        .broadcast(&message)
+        .await
+        .map_err(|err| err.to_string())?;
     Ok(())
 }
 
+#[tauri::command]
+async fn teleport_player(id: Uuid, position: [f32; 3]) -> Result<(), String> {
+    client()?
+        .teleport_player(id, position)
+        .await
+        .map_err(|err| err.to_string())?;
+    Ok(())
+}
+
+#[tauri::command]
+async fn mute_player(id: Uuid, duration_secs: Option<u64>) -> Result<(), String> {
+    client()?
+        .mute_player(id, duration_secs)
+        .await
+        .map_err(|err| err.to_string())?;
+    Ok(())
+}
+
+#[tauri::command]
+async fn unmute_player(id: Uuid) -> Result<(), String> {
+    client()?
+        .unmute_player(id)
+        .await
+        .map_err(|err| err.to_string())?;
+    Ok(())
+}
+
+#[tauri::command]
+async fn rename_server(name: String) -> Result<(), String> {
+    client()?
+        .rename_server(&name)
+        .await
+        .map_err(|err| err.to_string())?;
+    Ok(())
+}
+
+#[tauri::command]
+async fn get_chat_log() -> Result<Vec<ChatLogEntry>, String> {
+    let response = client()?
+        .get_chat_log(None, None)
+        .await
+        .map_err(|err| err.to_string())?;
+    Ok(response)
+}
+
+/// Forwards the events of the game server to the frontend, resubscribing when the stream ends.
+async fn forward_events(app: AppHandle) {
+    let client = match client() {
+        Ok(client) => client,
+        Err(err) => {
+            eprintln!("Not forwarding server events: {err}");
+            return;
+        }
+    };
+
+    loop {
+        match client.subscribe().await {
+            Ok(mut subscription) => loop {
+                match subscription.next().await {
+                    Ok(Some(event)) => {
+                        if let Err(err) = app.emit_all("server-event", event) {
+                            eprintln!("Could not forward server event: {err}");
+                        }
+                    }
+                    Ok(None) => break,
+                    Err(err) => {
+                        eprintln!("Server event stream failed: {err}");
+                        break;
+                    }
+                }
+            },
+            Err(err) => eprintln!("Could not subscribe to server events: {err}"),
+        }
+
+        tokio::time::sleep(Duration::from_secs(5)).await;
+    }
+}
+
 fn main() {
     tauri::Builder::default()
-        .invoke_handler(tauri::generate_handler![get_players, kick_player])
+        .setup(|app| {
+            tauri::async_runtime::spawn(forward_events(app.handle()));
+            Ok(())
+        })
+        .invoke_handler(tauri::generate_handler![
+            get_players,
+            get_player,
+            kick_player,
+            broadcast,
+            teleport_player,
+            mute_player,
+            unmute_player,
+            rename_server,
+            get_chat_log
+        ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
 }
@@ -1,45 +1,186 @@
-use models::server::api::PlayerResponse;
-use serde::{Deserialize, Serialize};
+use models::server::api::{
+    ChatLogEntry, MovementState, PlayerDetails, PlayerResponse, ServerEvent,
+};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
 use serde_wasm_bindgen::to_value;
+use std::rc::Rc;
 use uuid::Uuid;
 use wasm_bindgen::prelude::*;
 use wasm_bindgen_futures::spawn_local;
+use web_sys::HtmlInputElement;
 use yew::prelude::*;
 
 #[wasm_bindgen]
 extern "C" {
-    #[wasm_bindgen(js_namespace = ["window", "__TAURI__", "tauri"])]
-    async fn invoke(cmd: &str, args: JsValue) -> JsValue;
+    #[wasm_bindgen(catch, js_namespace = ["window", "__TAURI__", "tauri"])]
+    async fn invoke(cmd: &str, args: JsValue) -> Result<JsValue, JsValue>;
+
+    #[wasm_bindgen(js_namespace = ["window", "__TAURI__", "event"])]
+    async fn listen(event: &str, handler: &Closure<dyn FnMut(JsValue)>) -> JsValue;
+}
+
+/// How long the mute button mutes a player for.
+const MUTE_DURATION_SECS: u64 = 5 * 60;
+
+/// How many lines of the event log are kept.
+const MAX_LOG_LINES: usize = 100;
+
+#[derive(Deserialize)]
+struct TauriEvent<T> {
+    payload: T,
 }
 
 #[derive(Serialize, Deserialize)]
 struct GetPlayersArgs;
 
+#[derive(Serialize, Deserialize)]
+struct GetChatLogArgs;
+
 #[derive(Serialize, Deserialize)]
 struct KickPlayerArgs {
     id: Uuid,
 }
 
-async fn invoke_get_players() -> PlayerResponse {
-    let args = to_value(&GetPlayersArgs).unwrap();
-    let response = invoke("get_players", args).await;
-    serde_wasm_bindgen::from_value(response).unwrap()
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct MutePlayerArgs {
+    id: Uuid,
+    duration_secs: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct BroadcastArgs {
+    message: String,
+}
+
+/// Runs a command of the backend, failing with the message of its error.
+async fn call<T: DeserializeOwned>(cmd: &str, args: &impl Serialize) -> Result<T, String> {
+    let response = invoke(cmd, to_value(args).unwrap())
+        .await
+        .map_err(|err| err.as_string().unwrap_or_else(|| format!("{cmd} failed")))?;
+
+    serde_wasm_bindgen::from_value(response).map_err(|err| err.to_string())
+}
+
+async fn invoke_get_players() -> Result<PlayerResponse, String> {
+    call("get_players", &GetPlayersArgs).await
+}
+
+async fn invoke_get_chat_log() -> Result<Vec<ChatLogEntry>, String> {
+    call("get_chat_log", &GetChatLogArgs).await
+}
+
+async fn invoke_kick_player(id: Uuid) -> Result<(), String> {
+    call("kick_player", &KickPlayerArgs { id }).await
 }
 
-async fn invoke_kick_player(id: Uuid) {
-    let args = to_value(&KickPlayerArgs { id }).unwrap();
-    invoke("kick_player", args).await;
+async fn invoke_mute_player(id: Uuid, duration_secs: Option<u64>) -> Result<(), String> {
+    call("mute_player", &MutePlayerArgs { id, duration_secs }).await
+}
+
+async fn invoke_broadcast(message: String) -> Result<(), String> {
+    call("broadcast", &BroadcastArgs { message }).await
+}
+
+#[derive(Default, PartialEq)]
+struct ServerState {
+    players: Option<Vec<PlayerDetails>>,
+    log: Vec<String>,
+}
+
+enum ServerAction {
+    Loaded(Vec<PlayerDetails>),
+    /// Chat from before the manager was opened
+    ChatLogLoaded(Vec<ChatLogEntry>),
+    Event(ServerEvent),
+    /// A command failed with this message
+    Failed(String),
+}
+
+impl Reducible for ServerState {
+    type Action = ServerAction;
+
+    fn reduce(self: Rc<Self>, action: Self::Action) -> Rc<Self> {
+        let mut players = self.players.clone();
+        let mut log = self.log.clone();
+
+        let username = |players: &Option<Vec<PlayerDetails>>, user_id: Uuid| {
+            players
+                .iter()
+                .flatten()
+                .find(|player| player.user_id == user_id)
+                .map_or(user_id.to_string(), |player| player.username.clone())
+        };
+
+        match action {
+            ServerAction::Loaded(loaded) => players = Some(loaded),
+            ServerAction::Failed(message) => log.push(format!("Error: {message}")),
+            ServerAction::ChatLogLoaded(entries) => {
+                let lines = entries.into_iter().map(|entry| match entry.username {
+                    Some(username) => format!("[{}] {username}: {}", entry.channel, entry.message),
+                    None => format!("[{}] {}", entry.channel, entry.message),
+                });
+
+                log.splice(0..0, lines);
+            }
+            ServerAction::Event(ServerEvent::PlayerJoined { player }) => {
+                log.push(format!("{} joined", player.username));
+
+                let players = players.get_or_insert_with(Vec::new);
+                players.retain(|existing| existing.user_id != player.user_id);
+                players.push(player);
+            }
+            ServerAction::Event(ServerEvent::PlayerLeft { user_id }) => {
+                log.push(format!("{} left", username(&players, user_id)));
+
+                if let Some(players) = &mut players {
+                    players.retain(|player| player.user_id != user_id);
+                }
+            }
+            ServerAction::Event(ServerEvent::PlayerKicked { user_id, reason }) => {
+                log.push(format!(
+                    "{} was kicked: {reason}",
+                    username(&players, user_id)
+                ));
+            }
+            ServerAction::Event(ServerEvent::ChatMessage {
+                username,
+                channel,
+                message,
+                ..
+            }) => {
+                log.push(format!("[{channel}] {username}: {message}"));
+            }
+            ServerAction::Event(ServerEvent::Positions { players: snapshots }) => {
+                for player in players.iter_mut().flatten() {
+                    if let Some(snapshot) = snapshots
+                        .iter()
+                        .find(|snapshot| snapshot.user_id == player.user_id)
+                    {
+                        player.position = snapshot.position;
+                    }
+                }
+            }
+        }
+
+        let overflow = log.len().saturating_sub(MAX_LOG_LINES);
+        log.drain(..overflow);
+
+        Rc::new(Self { players, log })
+    }
 }
 
 #[function_component(App)]
 pub fn app() -> Html {
-    let players: UseStateHandle<Option<PlayerResponse>> = use_state(|| None);
+    let state = use_reducer(ServerState::default);
 
-    let load_players = use_callback(players.clone(), move |_, players| {
-        let players = players.clone();
+    let load_players = use_callback(state.dispatcher(), move |_, dispatcher| {
+        let dispatcher = dispatcher.clone();
         spawn_local(async move {
-            let value = invoke_get_players().await;
-            players.set(Some(value));
+            match invoke_get_players().await {
+                Ok(response) => dispatcher.dispatch(ServerAction::Loaded(response.players)),
+                Err(err) => dispatcher.dispatch(ServerAction::Failed(err)),
+            }
         });
     });
 
@@ -47,6 +188,31 @@ pub fn app() -> Html {
         load_players.emit(());
     });
 
+    use_effect_with(state.dispatcher(), move |dispatcher| {
+        let dispatcher = dispatcher.clone();
+        spawn_local(async move {
+            match invoke_get_chat_log().await {
+                Ok(entries) => dispatcher.dispatch(ServerAction::ChatLogLoaded(entries)),
+                Err(err) => dispatcher.dispatch(ServerAction::Failed(err)),
+            }
+        });
+    });
+
+    use_effect_with(state.dispatcher(), move |dispatcher| {
+        let dispatcher = dispatcher.clone();
+        let handler = Closure::<dyn FnMut(JsValue)>::new(move |event: JsValue| {
+            if let Ok(event) = serde_wasm_bindgen::from_value::<TauriEvent<ServerEvent>>(event) {
+                dispatcher.dispatch(ServerAction::Event(event.payload));
+            }
+        });
+
+        spawn_local(async move {
+            listen("server-event", &handler).await;
+            // The listener stays registered for as long as the app runs
+            handler.forget();
+        });
+    });
+
     let reload = use_callback(
         load_players.clone(),
         move |event: MouseEvent, load_players| {
@@ -55,46 +221,156 @@ pub fn app() -> Html {
         },
     );
 
-    let kick = use_callback(load_players.clone(), move |id: Uuid, load_players| {
-        let load_players = load_players.clone();
+    let kick = use_callback(
+        (load_players.clone(), state.dispatcher()),
+        move |id: Uuid, (load_players, dispatcher)| {
+            let load_players = load_players.clone();
+            let dispatcher = dispatcher.clone();
+            spawn_local(async move {
+                if let Err(err) = invoke_kick_player(id).await {
+                    dispatcher.dispatch(ServerAction::Failed(err));
+                }
+                load_players.emit(());
+            });
+        },
+    );
+
+    let mute = use_callback(state.dispatcher(), move |id: Uuid, dispatcher| {
+        let dispatcher = dispatcher.clone();
         spawn_local(async move {
-            invoke_kick_player(id).await;
-            load_players.emit(());
+            if let Err(err) = invoke_mute_player(id, Some(MUTE_DURATION_SECS)).await {
+                dispatcher.dispatch(ServerAction::Failed(err));
+            }
         });
     });
 
-    let player_list = use_memo(players.clone(), |players| {
-        let players = players.clone();
-        match players.as_ref() {
-            Some(response) => {
-                let players = response.players.clone();
-
-                players
-                    .into_iter()
-                    .enumerate()
-                    .map(|(index, player)| {
-                        let kick = kick.clone();
-                        let onclick = Callback::from(move |_| {
-                            kick.emit(player);
-                        });
-
-                        html! {
-                            <li key={index}>
-                                {player.to_string()}
+    let broadcast_input = use_node_ref();
+    let send_broadcast = use_callback(
+        (broadcast_input.clone(), state.dispatcher()),
+        move |event: SubmitEvent, (broadcast_input, dispatcher)| {
+            event.prevent_default();
+
+            let Some(input) = broadcast_input.cast::<HtmlInputElement>() else {
+                return;
+            };
+
+            let message = input.value();
+            if message.trim().is_empty() {
+                return;
+            }
+
+            input.set_value("");
+            let dispatcher = dispatcher.clone();
+            spawn_local(async move {
+                if let Err(err) = invoke_broadcast(message).await {
+                    dispatcher.dispatch(ServerAction::Failed(err));
+                }
+            });
+        },
+    );
+
+    let player_list = match &state.players {
+        Some(players) if !players.is_empty() => {
+            let rows = players
+                .iter()
+                .cloned()
+                .map(|player| {
+                    let kick = kick.clone();
+                    let mute = mute.clone();
+                    let user_id = player.user_id;
+                    let onclick = Callback::from(move |_| {
+                        kick.emit(user_id);
+                    });
+                    let onmute = Callback::from(move |_| {
+                        mute.emit(user_id);
+                    });
+
+                    let [x, y, z] = player.position;
+                    let rtt = player
+                        .rtt_ms
+                        .map_or(String::from("-"), |rtt| format!("{rtt} ms"));
+
+                    html! {
+                        <tr key={user_id.to_string()}>
+                            <td title={user_id.to_string()}>{player.username}</td>
+                            <td>{player.client_id}</td>
+                            <td>{format!("{x:.1}, {y:.1}, {z:.1}")}</td>
+                            <td>{player.connected_at.time().to_string()}</td>
+                            <td>{rtt}</td>
+                            <td>{format_bytes(player.bytes_sent)}</td>
+                            <td>{format_bytes(player.bytes_received)}</td>
+                            <td>{format_movement(&player.movement)}</td>
+                            <td>
+                                <button onclick={onmute}>{"Mute"}</button>
                                 <button onclick={onclick}>{"Kick"}</button>
-                            </li>
-                        }
-                    })
-                    .collect::<Html>()
+                            </td>
+                        </tr>
+                    }
+                })
+                .collect::<Html>();
+
+            html! {
+                <table>
+                    <thead>
+                        <tr>
+                            <th>{"Player"}</th>
+                            <th>{"Client"}</th>
+                            <th>{"Position"}</th>
+                            <th>{"Connected"}</th>
+                            <th>{"RTT"}</th>
+                            <th>{"Sent"}</th>
+                            <th>{"Received"}</th>
+                            <th>{"Moving"}</th>
+                            <th></th>
+                        </tr>
+                    </thead>
+                    <tbody>{rows}</tbody>
+                </table>
             }
-            None => html! { <p>{"No players"}</p> },
         }
-    });
+        _ => html! { <p>{"No players"}</p> },
+    };
+
+    let log = state
+        .log
+        .iter()
+        .map(|line| html! { <li>{line}</li> })
+        .collect::<Html>();
 
     html! {
         <main>
-            <ul>{(*player_list).clone()}</ul>
+            {player_list}
             <button type="submit" onclick={reload}>{"Reload"}</button>
+            <form onsubmit={send_broadcast}>
+                <input ref={broadcast_input} placeholder="Message all players..." />
+                <button type="submit">{"Broadcast"}</button>
+            </form>
+            <ul>{log}</ul>
         </main>
     }
 }
+
+fn format_bytes(bytes: u64) -> String {
+    match bytes {
+        0..1024 => format!("{bytes} B"),
+        1024..1_048_576 => format!("{:.1} KiB", bytes as f64 / 1024.0),
+        _ => format!("{:.1} MiB", bytes as f64 / 1_048_576.0),
+    }
+}
+
+fn format_movement(movement: &MovementState) -> String {
+    let directions: Vec<&str> = [
+        (movement.forward, "forward"),
+        (movement.backward, "backward"),
+        (movement.left, "left"),
+        (movement.right, "right"),
+    ]
+    .into_iter()
+    .filter_map(|(active, direction)| active.then_some(direction))
+    .collect();
+
+    match directions.is_empty() {
+        true => String::from("idle"),
+        false => directions.join(", "),
+    }
+}
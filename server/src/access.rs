@@ -0,0 +1,177 @@
+use anyhow::Result;
+use bevy_ecs::system::Resource;
+use engine::models::network::KickReason;
+use models::server::api::{Ban, NewBan, Whitelist};
+use serde::{Deserialize, Serialize};
+use std::{fs, path::PathBuf};
+use time::OffsetDateTime;
+use uuid::Uuid;
+
+/// The bans, whitelist and operators of the server, written to a JSON file whenever they change.
+#[derive(Resource)]
+pub struct AccessList {
+    path: PathBuf,
+    data: AccessData,
+}
+
+#[derive(Clone, Default, Deserialize, Serialize)]
+struct AccessData {
+    bans: Vec<Ban>,
+    whitelist: Whitelist,
+    /// Users allowed to use operator commands, on top of global moderators
+    #[serde(default)]
+    ops: Vec<Uuid>,
+}
+
+impl AccessList {
+    pub fn load(path: PathBuf) -> Result<Self> {
+        let data = match path.exists() {
+            true => serde_json::from_str(&fs::read_to_string(&path)?)?,
+            false => AccessData::default(),
+        };
+
+        Ok(Self { path, data })
+    }
+
+    /// Applies `change` to a copy of the data and swaps it in once saved, so a failed write
+    /// leaves the list as it was. Nothing is written when `change` returns false.
+    fn update(&mut self, change: impl FnOnce(&mut AccessData) -> bool) -> Result<bool> {
+        let mut data = self.data.clone();
+
+        if !change(&mut data) {
+            return Ok(false);
+        }
+
+        let now = OffsetDateTime::now_utc();
+        data.bans.retain(|ban| ban.is_active(now));
+
+        // Write next to the file first so a crash never leaves it half written.
+        let tmp = self.path.with_extension("tmp");
+        fs::write(&tmp, serde_json::to_string_pretty(&data)?)?;
+        fs::rename(tmp, &self.path)?;
+
+        self.data = data;
+
+        Ok(true)
+    }
+
+    /// Whether `user_id` may join, or why not.
+    pub fn check(&self, user_id: Uuid) -> Result<(), KickReason> {
+        let now = OffsetDateTime::now_utc();
+
+        if let Some(ban) = self
+            .data
+            .bans
+            .iter()
+            .find(|ban| ban.user_id == user_id && ban.is_active(now))
+        {
+            return Err(KickReason::Banned {
+                reason: ban.reason.clone(),
+            });
+        }
+
+        if self.data.whitelist.enabled && !self.data.whitelist.user_ids.contains(&user_id) {
+            return Err(KickReason::NotWhitelisted);
+        }
+
+        Ok(())
+    }
+
+    pub fn bans(&self) -> Vec<Ban> {
+        let now = OffsetDateTime::now_utc();
+
+        self.data
+            .bans
+            .iter()
+            .filter(|ban| ban.is_active(now))
+            .cloned()
+            .collect()
+    }
+
+    /// Bans a user, replacing an earlier ban of the same user.
+    pub fn ban(&mut self, new_ban: NewBan) -> Result<Ban> {
+        let ban = Ban {
+            user_id: new_ban.user_id,
+            reason: new_ban.reason,
+            created_at: OffsetDateTime::now_utc(),
+            expires_at: new_ban.expires_at,
+        };
+
+        self.update(|data| {
+            data.bans.retain(|existing| existing.user_id != ban.user_id);
+            data.bans.push(ban.clone());
+            true
+        })?;
+
+        Ok(ban)
+    }
+
+    pub fn unban(&mut self, user_id: Uuid) -> Result<bool> {
+        self.update(|data| {
+            let count = data.bans.len();
+            data.bans.retain(|ban| ban.user_id != user_id);
+            data.bans.len() != count
+        })
+    }
+
+    pub fn whitelist(&self) -> &Whitelist {
+        &self.data.whitelist
+    }
+
+    pub fn set_whitelist_enabled(&mut self, enabled: bool) -> Result<()> {
+        self.update(|data| {
+            data.whitelist.enabled = enabled;
+            true
+        })?;
+
+        Ok(())
+    }
+
+    pub fn add_to_whitelist(&mut self, user_id: Uuid) -> Result<()> {
+        self.update(|data| {
+            let added = !data.whitelist.user_ids.contains(&user_id);
+            if added {
+                data.whitelist.user_ids.push(user_id);
+            }
+            added
+        })?;
+
+        Ok(())
+    }
+
+    pub fn remove_from_whitelist(&mut self, user_id: Uuid) -> Result<bool> {
+        self.update(|data| {
+            let count = data.whitelist.user_ids.len();
+            data.whitelist.user_ids.retain(|id| *id != user_id);
+            data.whitelist.user_ids.len() != count
+        })
+    }
+
+    pub fn ops(&self) -> &[Uuid] {
+        &self.data.ops
+    }
+
+    pub fn is_op(&self, user_id: Uuid) -> bool {
+        self.data.ops.contains(&user_id)
+    }
+
+    pub fn add_op(&mut self, user_id: Uuid) -> Result<()> {
+        self.update(|data| {
+            let added = !data.ops.contains(&user_id);
+            if added {
+                data.ops.push(user_id);
+            }
+            added
+        })?;
+
+        Ok(())
+    }
+
+    pub fn remove_op(&mut self, user_id: Uuid) -> Result<bool> {
+        self.update(|data| {
+            let count = data.ops.len();
+            data.ops.retain(|id| *id != user_id);
+            data.ops.len() != count
+        })
+    }
+}
@@ -1,4 +1,5 @@
-use anyhow::Result;
+use access::AccessList;
+use anyhow::{anyhow, Result};
 use bevy::app::App;
 use bevy::prelude::PluginGroup;
 use bevy::MinimalPlugins;
@@ -9,28 +10,59 @@ use bevy::{
 };
 use bevy_ecs::prelude::*;
 use bevy_quinnet::server::QuinnetServer;
-use clap::{arg, Parser};
-use engine::models::network::ServerMessage;
+use clap::Parser;
+use engine::models::network::{KickReason, ServerMessage};
 use engine::{
-    api_client::{ping_server, register_server},
-    components::player::{Player, PlayerPosition},
+    api_client::{check_bans, deregister_server, ping_server, register_server, update_server},
+    components::{
+        movement::Movement,
+        player::{Player, PlayerPosition},
+    },
     plugins::movement::MovementPlugin,
+    tokens::TokenVerifier,
 };
 use futures::future::join_all;
-use models::api::servers::Server;
-use plugins::network::NetworkPlugin;
-use std::{net::IpAddr, time::Duration};
-use time::OffsetDateTime;
+use models::{
+    api::servers::{Server, ServerStatus},
+    data::servers::ServerKey,
+    server::api::{Ban, ChatLogEntry, ChatLogQuery, ChatStats, NewBan, PlayerDetails, Whitelist},
+};
+use plugins::{
+    chat::{broadcast_system_message, ChatPlugin},
+    commands::CommandsPlugin,
+    events::{player_details, EventsPlugin, ManagementEvents},
+    history::ChatLog,
+    input::InputPlugin,
+    moderation::{
+        ChatConfig, ChatCounters, ChatFiltersExt, LinkFilter, ModerationPlugin, WordFilter,
+    },
+    network::{kick_client, muted_message, Muted, NetworkPlugin, PlayerSession},
+};
+#[cfg(unix)]
+use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+use std::{
+    fs::{self, OpenOptions},
+    io::Write,
+    net::{IpAddr, Ipv4Addr, SocketAddr},
+    path::{Path, PathBuf},
+    str::FromStr,
+    time::Duration,
+};
 use tokio::{
     sync::{mpsc, oneshot},
-    time::sleep,
+    time::interval,
 };
+use tracing::{info, warn};
 use uuid::Uuid;
 use webserver::create_router;
 
+mod access;
 mod plugins;
 mod webserver;
 
+/// How many chat log messages the management api returns when not asked for a number.
+const CHAT_LOG_PAGE: usize = 100;
+
 #[derive(Parser, Debug)]
 #[command(version, about, long_about = None)]
 struct ServerArgs {
@@ -51,29 +83,163 @@ struct ServerArgs {
     /// The port to run the management web server on
     #[arg(short, long, default_value = "3001")]
     web_port: u16,
+
+    /// Only accept management connections from this machine
+    #[arg(long)]
+    web_localhost: bool,
+
+    /// The token the management web server requires as a bearer token
+    #[arg(long, env = "CQ_ADMIN_TOKEN")]
+    admin_token: Option<String>,
+
+    /// Where the generated admin token is kept when not passed explicitly
+    #[arg(long, default_value = "admin.token")]
+    admin_token_file: PathBuf,
+
+    /// Disconnect clients that haven't sent anything for this long, in seconds
+    #[arg(long, default_value = "30")]
+    idle_timeout: u64,
+
+    /// The issuer the api puts in its tokens
+    #[arg(long, default_value = "cq-api")]
+    api_issuer: String,
+
+    /// The api key of an existing registration of this server
+    #[arg(long, env = "CQ_SERVER_API_KEY")]
+    api_key: Option<String>,
+
+    /// Where the api key is kept between restarts when not passed explicitly
+    #[arg(long, default_value = "server.key")]
+    api_key_file: PathBuf,
+
+    /// An access token of the owner, used to register the server when it has no valid api key
+    #[arg(long, env = "CQ_OWNER_TOKEN")]
+    owner_token: Option<String>,
+
+    /// Accept api calls for this server from any address, for servers behind a NAT
+    #[arg(long)]
+    allow_any_addr: bool,
+
+    /// The number of players the server advertises room for
+    #[arg(long, default_value = "16")]
+    max_players: u32,
+
+    /// The game mode shown in the server browser
+    #[arg(long)]
+    game_mode: Option<String>,
+
+    /// The map shown in the server browser
+    #[arg(long)]
+    map: Option<String>,
+
+    /// The region the server is hosted in, used to filter the server browser
+    #[arg(long)]
+    region: Option<String>,
+
+    /// A free-form tag shown in the server browser, can be repeated
+    #[arg(long = "tag")]
+    tags: Vec<String>,
+
+    /// Where the bans and whitelist of the server are kept
+    #[arg(long, default_value = "access.json")]
+    access_file: PathBuf,
+
+    /// Messages a player can send in a row before being rate limited
+    #[arg(long, default_value = "5")]
+    chat_burst: u32,
+
+    /// Messages per second a player can keep sending
+    #[arg(long, default_value = "1.0")]
+    chat_rate: f32,
+
+    /// The most characters a chat message can have
+    #[arg(long, default_value = "256")]
+    chat_max_length: usize,
+
+    /// Chat violations in a row after which a player is muted automatically
+    #[arg(long, default_value = "3")]
+    chat_max_violations: u32,
+
+    /// How long players are muted automatically for, in seconds
+    #[arg(long, default_value = "60")]
+    chat_auto_mute: u64,
+
+    /// A file with words to mask in chat, one per line
+    #[arg(long)]
+    chat_word_list: Option<PathBuf>,
+
+    /// Drop chat messages that contain links
+    #[arg(long)]
+    chat_block_links: bool,
+
+    /// How many of the most recent chat messages the server keeps
+    #[arg(long, default_value = "500")]
+    chat_history: usize,
+
+    /// How many of them players get when they join
+    #[arg(long, default_value = "20")]
+    chat_history_on_join: usize,
+
+    /// A file to keep the chat history in between restarts
+    #[arg(long)]
+    chat_history_file: Option<PathBuf>,
 }
 
 enum AppMessage {
-    GetPlayers(oneshot::Sender<Vec<(Uuid, Vec3)>>),
+    GetPlayers(oneshot::Sender<Vec<PlayerDetails>>),
+    GetPlayer(Uuid, oneshot::Sender<Option<PlayerDetails>>),
     GetServer(oneshot::Sender<Option<Server>>),
-    KickPlayer(Uuid),
-    SetServer(Server),
+    KickPlayer {
+        id: Uuid,
+        reason: KickReason,
+    },
+    SetServer(Box<Server>),
+    /// Kicks every client, replying once they have been told
+    Shutdown(oneshot::Sender<()>),
+    GetBans(oneshot::Sender<Vec<Ban>>),
+    /// Bans a user and kicks them when they are playing
+    Ban(NewBan, oneshot::Sender<Result<Ban>>),
+    Unban(Uuid, oneshot::Sender<Result<bool>>),
+    GetWhitelist(oneshot::Sender<Whitelist>),
+    /// Sends a system chat message to every player
+    Broadcast(String),
+    Teleport(Uuid, Vec3, oneshot::Sender<bool>),
+    /// Mutes a player for the given duration, or until it leaves
+    Mute(Uuid, Option<Duration>, oneshot::Sender<bool>),
+    Unmute(Uuid, oneshot::Sender<bool>),
+    /// Takes over the server as re-announced under a new name and tells the players
+    Rename(Box<Server>),
+    /// Enabling the whitelist kicks every player that isn't on it
+    SetWhitelistEnabled(bool, oneshot::Sender<Result<()>>),
+    AddToWhitelist(Uuid, oneshot::Sender<Result<()>>),
+    RemoveFromWhitelist(Uuid, oneshot::Sender<Result<bool>>),
+    GetOps(oneshot::Sender<Vec<Uuid>>),
+    GetChatStats(oneshot::Sender<ChatStats>),
+    /// The chat log of the server on every channel, oldest first
+    GetChatLog(ChatLogQuery, oneshot::Sender<Vec<ChatLogEntry>>),
+    AddOp(Uuid, oneshot::Sender<Result<()>>),
+    RemoveOp(Uuid, oneshot::Sender<Result<bool>>),
+}
+
+/// What the management web server needs to re-announce the server to the api.
+#[derive(Clone)]
+struct Registration {
+    api_base_url: String,
+    api_key: String,
+    id: Uuid,
+    addr: IpAddr,
+    port: u16,
 }
 
 #[derive(Resource)]
 struct AppState {
     server: Option<Server>,
-    tx: mpsc::Sender<AppMessage>,
     rx: mpsc::Receiver<AppMessage>,
 }
 
 impl AppState {
-    fn new(tx: mpsc::Sender<AppMessage>, rx: mpsc::Receiver<AppMessage>) -> Self {
-        Self {
-            server: None,
-            tx,
-            rx,
-        }
+    fn new(rx: mpsc::Receiver<AppMessage>) -> Self {
+        Self { server: None, rx }
     }
 }
 
@@ -84,108 +250,506 @@ async fn main() -> Result<()> {
         .init();
 
     let (tx, rx) = mpsc::channel::<AppMessage>(32);
+    let events = ManagementEvents::new(256);
 
     let args = ServerArgs::parse();
 
     let port = args.port;
+    let web_addr = match args.web_localhost {
+        true => IpAddr::V4(Ipv4Addr::LOCALHOST),
+        false => IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+    };
     let web_port = args.web_port;
+    let admin_token = admin_token(&args)?;
+    let idle_timeout = Duration::from_secs(args.idle_timeout);
+    let max_players = args.max_players as usize;
+    let access_list = AccessList::load(args.access_file.clone())?;
+    let chat_config = ChatConfig {
+        burst: args.chat_burst,
+        rate: args.chat_rate,
+        max_length: args.chat_max_length,
+        max_violations: args.chat_max_violations,
+        auto_mute: Duration::from_secs(args.chat_auto_mute),
+    };
+    let word_filter = match &args.chat_word_list {
+        Some(path) => Some(WordFilter::new(
+            fs::read_to_string(path)?.lines().map(String::from),
+        )),
+        None => None,
+    };
+    let block_links = args.chat_block_links;
+    let chat_log = ChatLog::load(
+        args.chat_history_file.clone(),
+        args.chat_history,
+        args.chat_history_on_join,
+    )?;
 
-    let bevy_tx = tx.clone();
+    let ticket_verifier = TokenVerifier::new(args.api_base_url.clone(), args.api_issuer.clone());
+    let verifier_handle = tokio::spawn(ticket_verifier.clone().run());
 
+    let bevy_events = events.clone();
     let bevy_handle = tokio::spawn(async move {
-        App::new()
-            .add_plugins(MinimalPlugins.set(ScheduleRunnerPlugin::run_loop(
-                Duration::from_secs_f64(1.0 / 60.0),
-            )))
-            .insert_resource(AppState::new(bevy_tx, rx))
-            .add_plugins(NetworkPlugin::new(port))
-            .add_plugins(MovementPlugin)
-            .add_systems(Update, app_message_system)
-            .run();
+        let mut app = App::new();
+
+        app.add_plugins(MinimalPlugins.set(ScheduleRunnerPlugin::run_loop(
+            Duration::from_secs_f64(1.0 / 60.0),
+        )))
+        .insert_resource(AppState::new(rx))
+        .insert_resource(access_list)
+        .insert_resource(chat_log)
+        .add_plugins(EventsPlugin::new(bevy_events))
+        .add_plugins(CommandsPlugin)
+        .add_plugins(ChatPlugin)
+        .add_plugins(ModerationPlugin::new(chat_config))
+        .add_plugins(NetworkPlugin::new(
+            port,
+            ticket_verifier,
+            idle_timeout,
+            max_players,
+        ))
+        .add_plugins(MovementPlugin)
+        .add_plugins(InputPlugin)
+        .add_systems(Update, app_message_system);
+
+        if let Some(word_filter) = word_filter {
+            app.add_chat_filter(word_filter);
+        }
+
+        if block_links {
+            app.add_chat_filter(LinkFilter);
+        }
+
+        app.run();
     });
 
-    let api_base_url = args.api_base_url.clone();
-    let addr = args.addr;
-    let port = args.port;
-    let name = args.name.clone();
+    let (server, api_key) = register(&args).await?;
+    let id = server.id;
+
+    tx.send(AppMessage::SetServer(Box::new(server))).await?;
 
+    let api_base_url = args.api_base_url.clone();
+    let ping_api_key = api_key.clone();
+    let mut status = ServerStatus {
+        player_count: 0,
+        max_players: args.max_players,
+        game_mode: args.game_mode.clone(),
+        map: args.map.clone(),
+        version: Some(env!("CARGO_PKG_VERSION").to_string()),
+        region: args.region.clone(),
+        tags: args.tags.clone(),
+    };
     let api_tx = tx.clone();
     let api_handle = tokio::spawn(async move {
-        let server = register_server(
-            &api_base_url,
-            &models::api::servers::RegisterServer { addr, port, name },
-        )
-        .await
-        .unwrap();
+        let mut ticker = interval(Duration::from_secs(30));
 
-        let id = server.id;
-        let mut last_ping = OffsetDateTime::now_utc();
+        loop {
+            ticker.tick().await;
 
-        api_tx.send(AppMessage::SetServer(server)).await.unwrap();
+            let (players_tx, players_rx) = oneshot::channel();
+            api_tx
+                .send(AppMessage::GetPlayers(players_tx))
+                .await
+                .unwrap();
+            let user_ids: Vec<Uuid> = players_rx
+                .await
+                .unwrap()
+                .into_iter()
+                .map(|player| player.user_id)
+                .collect();
+            status.player_count = user_ids.len() as u32;
 
-        loop {
-            let now = OffsetDateTime::now_utc();
+            match ping_server(&api_base_url, &ping_api_key, &id, &status).await {
+                Ok(server) => api_tx
+                    .send(AppMessage::SetServer(Box::new(server)))
+                    .await
+                    .unwrap(),
+                Err(err) => warn!("Could not ping the api: {err}"),
+            }
 
-            if now - last_ping >= Duration::from_secs(30) {
-                let server = ping_server(&api_base_url, &id).await.unwrap();
-                api_tx.send(AppMessage::SetServer(server)).await.unwrap();
+            if user_ids.is_empty() {
+                continue;
             }
 
-            last_ping = OffsetDateTime::now_utc();
-            sleep(Duration::from_secs(30)).await;
+            // Players banned platform wide since they joined are removed here.
+            match check_bans(&api_base_url, &ping_api_key, &id, user_ids).await {
+                Ok(bans) => {
+                    for ban in bans {
+                        api_tx
+                            .send(AppMessage::KickPlayer {
+                                id: ban.user_id,
+                                reason: KickReason::Banned {
+                                    reason: Some(ban.message()),
+                                },
+                            })
+                            .await
+                            .unwrap();
+                    }
+                }
+                Err(err) => warn!("Could not check bans: {err}"),
+            }
         }
     });
 
     let webserver_tx = tx.clone();
+    let webserver_events = events.clone();
+    let registration = Registration {
+        api_base_url: args.api_base_url.clone(),
+        api_key: api_key.clone(),
+        id,
+        addr: args.addr,
+        port: args.port,
+    };
     let webserver_handle = tokio::spawn(async move {
-        let app = create_router(webserver_tx);
+        let app = create_router(webserver_tx, webserver_events, registration, admin_token);
 
-        let listener = tokio::net::TcpListener::bind(format!("0.0.0.0:{}", web_port))
+        let listener = tokio::net::TcpListener::bind(SocketAddr::new(web_addr, web_port))
             .await
             .unwrap();
 
         axum::serve(listener, app).await.unwrap();
     });
 
-    join_all([api_handle, bevy_handle, webserver_handle]).await;
+    tokio::select! {
+        _ = join_all([api_handle, bevy_handle, webserver_handle, verifier_handle]) => {}
+        _ = shutdown_signal() => {
+            info!("Shutting down, deregistering server {id}");
+
+            let (shutdown_tx, shutdown_rx) = oneshot::channel();
+            tx.send(AppMessage::Shutdown(shutdown_tx)).await?;
+            shutdown_rx.await?;
+
+            if let Err(err) = deregister_server(&args.api_base_url, &api_key, &id).await {
+                warn!("Could not deregister server: {err}");
+            }
+        }
+    }
 
     Ok(())
 }
 
+/// Resolves on ctrl-c, or when asked to stop by a process manager on unix.
+async fn shutdown_signal() {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+
+        match signal(SignalKind::terminate()) {
+            Ok(mut terminate) => {
+                tokio::select! {
+                    _ = tokio::signal::ctrl_c() => {}
+                    _ = terminate.recv() => {}
+                }
+            }
+            Err(err) => {
+                warn!("Could not listen for SIGTERM: {err}");
+                let _ = tokio::signal::ctrl_c().await;
+            }
+        }
+    }
+
+    #[cfg(not(unix))]
+    let _ = tokio::signal::ctrl_c().await;
+}
+
+/// Reuses the registration of the api key when it's still valid, registers the server with the
+/// owner token otherwise and keeps the new api key in the key file.
+async fn register(args: &ServerArgs) -> Result<(Server, String)> {
+    let api_key = match &args.api_key {
+        Some(api_key) => Some(api_key.clone()),
+        None => fs::read_to_string(&args.api_key_file)
+            .ok()
+            .map(|key| key.trim().to_string()),
+    };
+
+    if let Some(api_key) = api_key {
+        let key = ServerKey::from_str(&api_key).map_err(|_| anyhow!("invalid server api key"))?;
+
+        let result = update_server(
+            &args.api_base_url,
+            &api_key,
+            &key.id,
+            &models::api::servers::UpdateServer {
+                addr: args.addr,
+                port: args.port,
+                name: args.name.clone(),
+            },
+        )
+        .await;
+
+        match result {
+            Ok(server) => return Ok((server, api_key)),
+            Err(err) if args.owner_token.is_some() => {
+                warn!("Could not reuse registration {}: {err}", key.id);
+            }
+            Err(err) => return Err(err.into()),
+        }
+    }
+
+    let owner_token = args
+        .owner_token
+        .as_ref()
+        .ok_or(anyhow!("an api key or owner token is required"))?;
+
+    let registered = register_server(
+        &args.api_base_url,
+        owner_token,
+        &models::api::servers::RegisterServer {
+            addr: args.addr,
+            port: args.port,
+            name: args.name.clone(),
+            allow_any_addr: args.allow_any_addr,
+        },
+    )
+    .await?;
+
+    info!("Registered server {}", registered.server.id);
+
+    if args.api_key.is_none() {
+        write_secret(&args.api_key_file, &registered.api_key)?;
+    }
+
+    Ok((registered.server, registered.api_key))
+}
+
+/// Reads the admin token from the arguments or the token file, generating one on first start.
+fn admin_token(args: &ServerArgs) -> Result<String> {
+    if let Some(token) = &args.admin_token {
+        return Ok(token.clone());
+    }
+
+    if let Ok(token) = fs::read_to_string(&args.admin_token_file) {
+        let token = token.trim();
+
+        if !token.is_empty() {
+            return Ok(token.to_string());
+        }
+    }
+
+    let token = format!("{}{}", Uuid::new_v4().simple(), Uuid::new_v4().simple());
+    write_secret(&args.admin_token_file, &token)?;
+
+    info!(
+        "Generated an admin token, saved to {}",
+        args.admin_token_file.display()
+    );
+
+    Ok(token)
+}
+
+/// Writes a secret to a file only its owner can read.
+fn write_secret(path: &Path, secret: &str) -> Result<()> {
+    let mut options = OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+
+    #[cfg(unix)]
+    options.mode(0o600);
+
+    let mut file = options.open(path)?;
+
+    // The mode only applies to new files, an existing one may be readable by others.
+    #[cfg(unix)]
+    file.set_permissions(fs::Permissions::from_mode(0o600))?;
+
+    file.write_all(secret.as_bytes())?;
+
+    Ok(())
+}
+
+#[allow(clippy::too_many_arguments)]
 fn app_message_system(
     mut commands: Commands,
-    players: Query<(Entity, &Player, &PlayerPosition)>,
+    players: Query<(Entity, &Player)>,
+    mut details: Query<(&Player, &mut PlayerPosition, &PlayerSession, &Movement)>,
     mut state: ResMut<AppState>,
     mut server: ResMut<QuinnetServer>,
+    mut access_list: ResMut<AccessList>,
+    chat_counters: Res<ChatCounters>,
+    chat_log: Res<ChatLog>,
 ) {
     if let Ok(message) = state.rx.try_recv() {
         match message {
             AppMessage::SetServer(server) => {
-                state.server = Some(server);
+                state.server = Some(*server);
             }
             AppMessage::GetServer(tx) => {
                 tx.send(state.server.clone()).unwrap();
             }
             AppMessage::GetPlayers(tx) => {
-                let ids = players
-                    .into_iter()
-                    .map(|(_, player, position)| (player.user_id, position.0))
+                let details = details
+                    .iter()
+                    .map(|player| player_details(server.endpoint(), player))
                     .collect();
 
-                tx.send(ids).unwrap();
+                tx.send(details).unwrap();
+            }
+            AppMessage::GetPlayer(id, tx) => {
+                let details = details
+                    .iter()
+                    .find(|(player, _, _, _)| player.user_id == id)
+                    .map(|player| player_details(server.endpoint(), player));
+
+                tx.send(details).unwrap();
             }
-            AppMessage::KickPlayer(id) => {
-                if let Some((entity, player, _)) =
-                    players.iter().find(|(_, player, _)| player.user_id == id)
+            AppMessage::KickPlayer { id, reason } => {
+                if let Some((entity, player)) =
+                    players.iter().find(|(_, player)| player.user_id == id)
                 {
-                    commands.entity(entity).despawn();
-                    let endpoint = server.endpoint_mut();
-                    endpoint
-                        .broadcast_message(ServerMessage::ClientDisconnected {
-                            client_id: player.client_id,
-                        })
-                        .unwrap();
+                    kick_client(
+                        &mut commands,
+                        server.endpoint_mut(),
+                        Some(entity),
+                        player.client_id,
+                        reason,
+                    );
+                }
+            }
+            AppMessage::Shutdown(tx) => {
+                let endpoint = server.endpoint_mut();
+
+                for client_id in endpoint.clients() {
+                    let entity = players
+                        .iter()
+                        .find(|(_, player)| player.client_id == client_id)
+                        .map(|(entity, _)| entity);
+
+                    kick_client(
+                        &mut commands,
+                        endpoint,
+                        entity,
+                        client_id,
+                        KickReason::ServerShutdown,
+                    );
                 }
+
+                tx.send(()).unwrap();
+            }
+            AppMessage::GetBans(tx) => {
+                tx.send(access_list.bans()).unwrap();
+            }
+            AppMessage::Ban(new_ban, tx) => {
+                let result = access_list.ban(new_ban);
+
+                if let Ok(ban) = &result {
+                    if let Some((entity, player)) = players
+                        .iter()
+                        .find(|(_, player)| player.user_id == ban.user_id)
+                    {
+                        kick_client(
+                            &mut commands,
+                            server.endpoint_mut(),
+                            Some(entity),
+                            player.client_id,
+                            KickReason::Banned {
+                                reason: ban.reason.clone(),
+                            },
+                        );
+                    }
+                }
+
+                tx.send(result).unwrap();
+            }
+            AppMessage::Unban(user_id, tx) => {
+                tx.send(access_list.unban(user_id)).unwrap();
+            }
+            AppMessage::GetWhitelist(tx) => {
+                tx.send(access_list.whitelist().clone()).unwrap();
+            }
+            AppMessage::SetWhitelistEnabled(enabled, tx) => {
+                let result = access_list.set_whitelist_enabled(enabled);
+
+                if result.is_ok() {
+                    for (entity, player) in players.iter() {
+                        if let Err(reason) = access_list.check(player.user_id) {
+                            kick_client(
+                                &mut commands,
+                                server.endpoint_mut(),
+                                Some(entity),
+                                player.client_id,
+                                reason,
+                            );
+                        }
+                    }
+                }
+
+                tx.send(result).unwrap();
+            }
+            AppMessage::AddToWhitelist(user_id, tx) => {
+                tx.send(access_list.add_to_whitelist(user_id)).unwrap();
+            }
+            AppMessage::RemoveFromWhitelist(user_id, tx) => {
+                tx.send(access_list.remove_from_whitelist(user_id)).unwrap();
+            }
+            AppMessage::GetOps(tx) => {
+                tx.send(access_list.ops().to_vec()).unwrap();
+            }
+            AppMessage::GetChatStats(tx) => {
+                tx.send(chat_counters.0.clone()).unwrap();
+            }
+            AppMessage::GetChatLog(query, tx) => {
+                let limit = query
+                    .limit
+                    .unwrap_or(CHAT_LOG_PAGE)
+                    .min(chat_log.capacity());
+                let (page, _) = chat_log.page(query.before, limit, |_| true);
+
+                tx.send(
+                    page.into_iter()
+                        .map(|(seq, record)| record.log_entry(seq))
+                        .collect(),
+                )
+                .unwrap();
+            }
+            AppMessage::AddOp(user_id, tx) => {
+                tx.send(access_list.add_op(user_id)).unwrap();
+            }
+            AppMessage::RemoveOp(user_id, tx) => {
+                tx.send(access_list.remove_op(user_id)).unwrap();
+            }
+            AppMessage::Broadcast(message) => {
+                commands.add(move |world: &mut World| broadcast_system_message(world, message));
+            }
+            AppMessage::Teleport(id, position, tx) => {
+                let teleported = match details
+                    .iter_mut()
+                    .find(|(player, _, _, _)| player.user_id == id)
+                {
+                    Some((_, mut player_position, _, _)) => {
+                        player_position.0 = position;
+                        true
+                    }
+                    None => false,
+                };
+
+                tx.send(teleported).unwrap();
+            }
+            AppMessage::Mute(id, duration, tx) => {
+                let player = players.iter().find(|(_, player)| player.user_id == id);
+
+                if let Some((entity, player)) = player {
+                    commands.entity(entity).insert(Muted::new(duration));
+
+                    server.endpoint_mut().try_send_message(
+                        player.client_id,
+                        ServerMessage::system(muted_message(duration)),
+                    );
+                }
+
+                tx.send(player.is_some()).unwrap();
+            }
+            AppMessage::Unmute(id, tx) => {
+                let player = players.iter().find(|(_, player)| player.user_id == id);
+
+                if let Some((entity, _)) = player {
+                    commands.entity(entity).remove::<Muted>();
+                }
+
+                tx.send(player.is_some()).unwrap();
+            }
+            AppMessage::Rename(server_info) => {
+                let message = format!("The server is now called {}", server_info.name);
+                state.server = Some(*server_info);
+
+                commands.add(move |world: &mut World| broadcast_system_message(world, message));
             }
         }
     }
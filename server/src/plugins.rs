@@ -1 +1,7 @@
+pub mod chat;
+pub mod commands;
+pub mod events;
+pub mod history;
+pub mod input;
+pub mod moderation;
 pub mod network;
@@ -0,0 +1,258 @@
+use bevy::app::{App, Plugin};
+use bevy_ecs::prelude::*;
+use bevy_quinnet::{server::QuinnetServer, shared::ClientId};
+use engine::{
+    components::player::Player,
+    models::network::{ChatChannel, ServerMessage},
+};
+use models::server::api::ServerEvent;
+use time::OffsetDateTime;
+
+use super::{
+    commands::{run_chat_command, ChatCommand, ChatCommandsExt, CommandContext, CommandResult},
+    events::ManagementEvents,
+    history::{ChatLog, ChatRecord},
+    moderation::{moderate_message, take_chat_token},
+    network::{Muted, PlayerSession},
+};
+
+/// The team a player talks to on [`ChatChannel::Team`].
+#[derive(Component)]
+pub struct PlayerTeam(pub String);
+
+pub struct ChatPlugin;
+
+impl Plugin for ChatPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_chat_command(ChatCommand {
+            name: "team",
+            usage: "[name]",
+            description: "Joins a team to chat with, leaves yours without a name",
+            op_only: false,
+            handler: team,
+        });
+    }
+}
+
+/// Runs commands and sends everything else as a chat message.
+pub fn handle_chat_message(
+    world: &mut World,
+    client_id: ClientId,
+    channel: ChatChannel,
+    message: String,
+) {
+    let Some(entity) = world
+        .query::<(Entity, &Player)>()
+        .iter(world)
+        .find(|(_, player)| player.client_id == client_id)
+        .map(|(entity, _)| entity)
+    else {
+        return;
+    };
+
+    // Muted players are told so before anything counts against them.
+    if !message.starts_with('/') {
+        if let Err(reason) = ensure_not_muted(world, entity) {
+            send_system_message(world, client_id, reason);
+            return;
+        }
+    }
+
+    if !take_chat_token(world, entity, client_id) {
+        return;
+    }
+
+    match message.strip_prefix('/') {
+        Some(input) => run_chat_command(world, client_id, input),
+        None => send_player_message(world, client_id, channel, message),
+    }
+}
+
+/// Delivers a chat message of the player of `client_id` only to the players on `channel`.
+pub fn send_player_message(
+    world: &mut World,
+    client_id: ClientId,
+    channel: ChatChannel,
+    message: String,
+) {
+    let Some((entity, user_id, username)) = world
+        .query::<(Entity, &Player, &PlayerSession)>()
+        .iter(world)
+        .find(|(_, player, _)| player.client_id == client_id)
+        .map(|(entity, player, session)| (entity, player.user_id, session.username.clone()))
+    else {
+        return;
+    };
+
+    let checked = ensure_not_muted(world, entity)
+        .and_then(|_| recipients(world, entity, client_id, channel))
+        .and_then(|recipients| {
+            moderate_message(world, entity, client_id, message).map(|message| (recipients, message))
+        });
+
+    let (recipients, message) = match checked {
+        Ok(checked) => checked,
+        Err(reason) => {
+            send_system_message(world, client_id, reason);
+            return;
+        }
+    };
+
+    let sent_at = OffsetDateTime::now_utc();
+
+    send_chat_message(
+        world,
+        &recipients,
+        Some(client_id),
+        channel,
+        message.clone(),
+        sent_at,
+    );
+
+    let team = match channel {
+        ChatChannel::Team => world.get::<PlayerTeam>(entity).map(|team| team.0.clone()),
+        _ => None,
+    };
+
+    world.resource_mut::<ChatLog>().push(ChatRecord {
+        user_id: Some(user_id),
+        username: Some(username.clone()),
+        channel,
+        team,
+        message: message.clone(),
+        sent_at,
+    });
+
+    world
+        .resource::<ManagementEvents>()
+        .send(ServerEvent::ChatMessage {
+            user_id,
+            username,
+            channel: channel.to_string(),
+            message,
+        });
+}
+
+/// Who should get a message the player of `entity` sent on `channel`.
+fn recipients(
+    world: &mut World,
+    entity: Entity,
+    client_id: ClientId,
+    channel: ChatChannel,
+) -> Result<Vec<ClientId>, String> {
+    match channel {
+        ChatChannel::Global => Ok(world
+            .query::<&Player>()
+            .iter(world)
+            .map(|player| player.client_id)
+            .collect()),
+        ChatChannel::Team => {
+            let team = world
+                .get::<PlayerTeam>(entity)
+                .map(|team| team.0.clone())
+                .ok_or("You are not on a team, join one with /team <name>")?;
+
+            Ok(world
+                .query::<(&Player, &PlayerTeam)>()
+                .iter(world)
+                .filter(|(_, player_team)| player_team.0 == team)
+                .map(|(player, _)| player.client_id)
+                .collect())
+        }
+        ChatChannel::Whisper(user_id) => {
+            let target = world
+                .query::<&Player>()
+                .iter(world)
+                .find(|player| player.user_id == user_id)
+                .map(|player| player.client_id)
+                .ok_or("That player is not here anymore")?;
+
+            match target == client_id {
+                true => Err(String::from("You can't whisper to yourself")),
+                false => Ok(vec![client_id, target]),
+            }
+        }
+        ChatChannel::System => Err(String::from("Only the server can send system messages")),
+    }
+}
+
+pub fn send_chat_message(
+    world: &World,
+    recipients: &[ClientId],
+    sender: Option<ClientId>,
+    channel: ChatChannel,
+    message: String,
+    sent_at: OffsetDateTime,
+) {
+    world
+        .resource::<QuinnetServer>()
+        .endpoint()
+        .try_send_group_message(
+            recipients.iter(),
+            ServerMessage::ChatMessage {
+                client_id: sender,
+                channel,
+                message,
+                sent_at,
+            },
+        );
+}
+
+/// Sends a message from the server to every player, keeping it in the chat log.
+pub fn broadcast_system_message(world: &mut World, message: impl Into<String>) {
+    let message = message.into();
+    let sent_at = OffsetDateTime::now_utc();
+
+    world
+        .resource::<QuinnetServer>()
+        .endpoint()
+        .try_broadcast_message(ServerMessage::ChatMessage {
+            client_id: None,
+            channel: ChatChannel::System,
+            message: message.clone(),
+            sent_at,
+        });
+
+    world.resource_mut::<ChatLog>().push(ChatRecord {
+        user_id: None,
+        username: None,
+        channel: ChatChannel::System,
+        team: None,
+        message,
+        sent_at,
+    });
+}
+
+/// Sends a message from the server to a single player.
+pub fn send_system_message(world: &World, client_id: ClientId, message: impl Into<String>) {
+    world
+        .resource::<QuinnetServer>()
+        .endpoint()
+        .try_send_message(client_id, ServerMessage::system(message));
+}
+
+pub fn ensure_not_muted(world: &World, entity: Entity) -> Result<(), String> {
+    match world.get::<Muted>(entity) {
+        Some(muted) if muted.is_active(OffsetDateTime::now_utc()) => {
+            Err(String::from("You are muted"))
+        }
+        _ => Ok(()),
+    }
+}
+
+fn team(world: &mut World, context: &CommandContext) -> CommandResult {
+    let name = context.args.trim();
+
+    if name.is_empty() {
+        return match world.entity_mut(context.entity).take::<PlayerTeam>() {
+            Some(team) => Ok(Some(format!("You left team {}", team.0))),
+            None => Err(String::from("Usage: /team <name>")),
+        };
+    }
+
+    world
+        .entity_mut(context.entity)
+        .insert(PlayerTeam(name.to_string()));
+
+    Ok(Some(format!("You joined team {name}")))
+}
@@ -0,0 +1,465 @@
+use bevy::{
+    app::{App, Plugin},
+    math::Vec3,
+};
+use bevy_ecs::prelude::*;
+use bevy_quinnet::{
+    server::{Endpoint, QuinnetServer},
+    shared::ClientId,
+};
+use engine::{
+    components::player::{Player, PlayerPosition, PlayerRole},
+    models::network::{ChatChannel, KickReason},
+};
+use models::{api::users::Role, server::api::NewBan};
+use std::{collections::BTreeMap, time::Duration};
+use uuid::Uuid;
+
+use crate::access::AccessList;
+
+use super::{
+    chat::{broadcast_system_message, ensure_not_muted, send_player_message, send_system_message},
+    moderation::moderate_message,
+    network::{
+        is_valid_position, kick_client, muted_message, Muted, PlayerSession, MAX_MUTE_DURATION,
+    },
+};
+
+/// What a command replies to the player that ran it, the message of the error when it failed.
+pub type CommandResult = Result<Option<String>, String>;
+
+pub type CommandHandler = fn(&mut World, &CommandContext) -> CommandResult;
+
+/// A command players run by sending `/<name>` in chat.
+#[derive(Clone)]
+pub struct ChatCommand {
+    pub name: &'static str,
+    /// The arguments as shown by `/help`, like `<user> [reason]`
+    pub usage: &'static str,
+    pub description: &'static str,
+    /// Only operators of the server and global moderators can run it
+    pub op_only: bool,
+    pub handler: CommandHandler,
+}
+
+/// The player that ran a command and what it passed.
+pub struct CommandContext {
+    pub entity: Entity,
+    pub client_id: ClientId,
+    pub user_id: Uuid,
+    pub username: String,
+    /// Everything after the name of the command
+    pub args: String,
+}
+
+/// Every command players can run, other plugins add theirs with [`ChatCommandsExt`].
+#[derive(Default, Resource)]
+pub struct ChatCommands(BTreeMap<&'static str, ChatCommand>);
+
+impl ChatCommands {
+    /// Adds a command, replacing an earlier one with the same name.
+    pub fn register(&mut self, command: ChatCommand) {
+        self.0.insert(command.name, command);
+    }
+
+    pub fn get(&self, name: &str) -> Option<&ChatCommand> {
+        self.0.get(name)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &ChatCommand> {
+        self.0.values()
+    }
+}
+
+pub trait ChatCommandsExt {
+    fn add_chat_command(&mut self, command: ChatCommand) -> &mut Self;
+}
+
+impl ChatCommandsExt for App {
+    fn add_chat_command(&mut self, command: ChatCommand) -> &mut Self {
+        self.world_mut()
+            .get_resource_or_insert_with(ChatCommands::default)
+            .register(command);
+        self
+    }
+}
+
+pub struct CommandsPlugin;
+
+impl Plugin for CommandsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ChatCommands>()
+            .add_chat_command(ChatCommand {
+                name: "help",
+                usage: "",
+                description: "Lists the commands you can use",
+                op_only: false,
+                handler: help,
+            })
+            .add_chat_command(ChatCommand {
+                name: "who",
+                usage: "",
+                description: "Lists who is playing",
+                op_only: false,
+                handler: who,
+            })
+            .add_chat_command(ChatCommand {
+                name: "w",
+                usage: "<user> <message>",
+                description: "Sends a message only the user sees",
+                op_only: false,
+                handler: whisper,
+            })
+            .add_chat_command(ChatCommand {
+                name: "me",
+                usage: "<action>",
+                description: "Tells everyone what you are doing",
+                op_only: false,
+                handler: me,
+            })
+            .add_chat_command(ChatCommand {
+                name: "kick",
+                usage: "<user> [reason]",
+                description: "Removes a player from the server",
+                op_only: true,
+                handler: kick,
+            })
+            .add_chat_command(ChatCommand {
+                name: "ban",
+                usage: "<user> [reason]",
+                description: "Bans a player from the server",
+                op_only: true,
+                handler: ban,
+            })
+            .add_chat_command(ChatCommand {
+                name: "tp",
+                usage: "<user> (<x> <y> <z> | <user>)",
+                description: "Moves a player to a position or another player",
+                op_only: true,
+                handler: teleport,
+            })
+            .add_chat_command(ChatCommand {
+                name: "mute",
+                usage: "<user> [seconds]",
+                description: "Keeps a player from chatting",
+                op_only: true,
+                handler: mute,
+            });
+    }
+}
+
+/// Runs the command `input` of the player of `client_id`, replying only to that player.
+pub fn run_chat_command(world: &mut World, client_id: ClientId, input: &str) {
+    let Some((entity, user_id, username)) = world
+        .query::<(Entity, &Player, &PlayerSession)>()
+        .iter(world)
+        .find(|(_, player, _)| player.client_id == client_id)
+        .map(|(entity, player, session)| (entity, player.user_id, session.username.clone()))
+    else {
+        return;
+    };
+
+    let (name, args) = split_arg(input);
+
+    let Some(command) = world.resource::<ChatCommands>().get(name).cloned() else {
+        send_system_message(
+            world,
+            client_id,
+            format!("Unknown command /{name}, see /help"),
+        );
+        return;
+    };
+
+    if command.op_only && !is_op(world, entity, user_id) {
+        send_system_message(world, client_id, format!("Only operators can use /{name}"));
+        return;
+    }
+
+    let context = CommandContext {
+        entity,
+        client_id,
+        user_id,
+        username,
+        args: args.to_string(),
+    };
+
+    match (command.handler)(world, &context) {
+        Ok(Some(message)) | Err(message) => send_system_message(world, client_id, message),
+        Ok(None) => {}
+    }
+}
+
+/// Whether the player is an operator of this server or a global moderator.
+pub fn is_op(world: &World, entity: Entity, user_id: Uuid) -> bool {
+    world
+        .get::<PlayerRole>(entity)
+        .is_some_and(|role| role.0 >= Role::Moderator)
+        || world.resource::<AccessList>().is_op(user_id)
+}
+
+/// How much say a player has on this server: global roles first, then operators of this server.
+fn rank(world: &World, entity: Entity, user_id: Uuid) -> (Role, bool) {
+    let role = world
+        .get::<PlayerRole>(entity)
+        .map_or(Role::Player, |role| role.0);
+
+    (
+        role,
+        role == Role::Player && world.resource::<AccessList>().is_op(user_id),
+    )
+}
+
+/// Refuses to act on players ranked the same as or above whoever issued the command.
+fn ensure_outranks(
+    world: &World,
+    context: &CommandContext,
+    target: &Target,
+    action: &str,
+) -> Result<(), String> {
+    let issuer = rank(world, context.entity, context.user_id);
+
+    if rank(world, target.entity, target.user_id) >= issuer {
+        return Err(format!("You can not {action} {}", target.username));
+    }
+
+    Ok(())
+}
+
+struct Target {
+    entity: Entity,
+    client_id: ClientId,
+    user_id: Uuid,
+    username: String,
+}
+
+/// Finds a player by username, ignoring case.
+fn find_player(world: &mut World, username: &str) -> Result<Target, String> {
+    world
+        .query::<(Entity, &Player, &PlayerSession)>()
+        .iter(world)
+        .find(|(_, _, session)| session.username.eq_ignore_ascii_case(username))
+        .map(|(entity, player, session)| Target {
+            entity,
+            client_id: player.client_id,
+            user_id: player.user_id,
+            username: session.username.clone(),
+        })
+        .ok_or_else(|| format!("Nobody called {username} is playing"))
+}
+
+/// Splits off the first word of `args`.
+fn split_arg(args: &str) -> (&str, &str) {
+    let args = args.trim();
+
+    match args.split_once(char::is_whitespace) {
+        Some((first, rest)) => (first, rest.trim_start()),
+        None => (args, ""),
+    }
+}
+
+/// Gives `f` the endpoint and commands that are applied right after it returns.
+fn with_endpoint<T>(world: &mut World, f: impl FnOnce(&mut Commands, &mut Endpoint) -> T) -> T {
+    let result = world.resource_scope(|world, mut server: Mut<QuinnetServer>| {
+        let mut commands = world.commands();
+        f(&mut commands, server.endpoint_mut())
+    });
+
+    world.flush();
+    result
+}
+
+fn help(world: &mut World, context: &CommandContext) -> CommandResult {
+    let op = is_op(world, context.entity, context.user_id);
+
+    let lines: Vec<String> = world
+        .resource::<ChatCommands>()
+        .iter()
+        .filter(|command| op || !command.op_only)
+        .map(|command| match command.usage.is_empty() {
+            true => format!("/{} - {}", command.name, command.description),
+            false => format!(
+                "/{} {} - {}",
+                command.name, command.usage, command.description
+            ),
+        })
+        .collect();
+
+    Ok(Some(lines.join("\n")))
+}
+
+fn who(world: &mut World, _context: &CommandContext) -> CommandResult {
+    let mut usernames: Vec<String> = world
+        .query::<&PlayerSession>()
+        .iter(world)
+        .map(|session| session.username.clone())
+        .collect();
+    usernames.sort_unstable_by_key(|username| username.to_lowercase());
+
+    Ok(Some(format!(
+        "{} playing: {}",
+        usernames.len(),
+        usernames.join(", ")
+    )))
+}
+
+fn whisper(world: &mut World, context: &CommandContext) -> CommandResult {
+    let (username, message) = split_arg(&context.args);
+
+    if username.is_empty() || message.is_empty() {
+        return Err(String::from("Usage: /w <user> <message>"));
+    }
+
+    let target = find_player(world, username)?;
+
+    send_player_message(
+        world,
+        context.client_id,
+        ChatChannel::Whisper(target.user_id),
+        message.to_string(),
+    );
+
+    Ok(None)
+}
+
+fn me(world: &mut World, context: &CommandContext) -> CommandResult {
+    let action = context.args.trim();
+
+    if action.is_empty() {
+        return Err(String::from("Usage: /me <action>"));
+    }
+
+    ensure_not_muted(world, context.entity)?;
+    let action = moderate_message(world, context.entity, context.client_id, action.to_string())?;
+
+    broadcast_system_message(world, format!("* {} {action}", context.username));
+
+    Ok(None)
+}
+
+fn kick(world: &mut World, context: &CommandContext) -> CommandResult {
+    let (username, reason) = split_arg(&context.args);
+
+    if username.is_empty() {
+        return Err(String::from("Usage: /kick <user> [reason]"));
+    }
+
+    let target = find_player(world, username)?;
+    ensure_outranks(world, context, &target, "kick")?;
+    let reason = (!reason.is_empty()).then(|| reason.to_string());
+
+    with_endpoint(world, |commands, endpoint| {
+        kick_client(
+            commands,
+            endpoint,
+            Some(target.entity),
+            target.client_id,
+            KickReason::Kicked { reason },
+        );
+    });
+
+    Ok(Some(format!("Kicked {}", target.username)))
+}
+
+fn ban(world: &mut World, context: &CommandContext) -> CommandResult {
+    let (username, reason) = split_arg(&context.args);
+
+    if username.is_empty() {
+        return Err(String::from("Usage: /ban <user> [reason]"));
+    }
+
+    let target = find_player(world, username)?;
+    ensure_outranks(world, context, &target, "ban")?;
+    let reason = (!reason.is_empty()).then(|| reason.to_string());
+
+    world
+        .resource_mut::<AccessList>()
+        .ban(NewBan {
+            user_id: target.user_id,
+            reason: reason.clone(),
+            expires_at: None,
+        })
+        .map_err(|err| format!("Could not ban {}: {err}", target.username))?;
+
+    with_endpoint(world, |commands, endpoint| {
+        kick_client(
+            commands,
+            endpoint,
+            Some(target.entity),
+            target.client_id,
+            KickReason::Banned { reason },
+        );
+    });
+
+    Ok(Some(format!("Banned {}", target.username)))
+}
+
+fn teleport(world: &mut World, context: &CommandContext) -> CommandResult {
+    let usage = || String::from("Usage: /tp <user> (<x> <y> <z> | <user>)");
+    let (username, rest) = split_arg(&context.args);
+
+    if username.is_empty() {
+        return Err(usage());
+    }
+
+    let target = find_player(world, username)?;
+    let parse = |value: &str| value.parse::<f32>().map_err(|_| usage());
+
+    let position = match rest.split_whitespace().collect::<Vec<_>>().as_slice() {
+        [x, y, z] => {
+            let position = Vec3::new(parse(x)?, parse(y)?, parse(z)?);
+            if !is_valid_position(position) {
+                return Err(usage());
+            }
+
+            position
+        }
+        [other] => {
+            let other = find_player(world, other)?;
+
+            world
+                .get::<PlayerPosition>(other.entity)
+                .map(|position| position.0)
+                .ok_or_else(|| format!("{} has no position", other.username))?
+        }
+        _ => return Err(usage()),
+    };
+
+    if let Some(mut player_position) = world.get_mut::<PlayerPosition>(target.entity) {
+        player_position.0 = position;
+    }
+
+    Ok(Some(format!(
+        "Teleported {} to {:.1}, {:.1}, {:.1}",
+        target.username, position.x, position.y, position.z
+    )))
+}
+
+fn mute(world: &mut World, context: &CommandContext) -> CommandResult {
+    let usage = || String::from("Usage: /mute <user> [seconds]");
+    let (username, seconds) = split_arg(&context.args);
+
+    if username.is_empty() {
+        return Err(usage());
+    }
+
+    let duration = match seconds.is_empty() {
+        true => None,
+        false => Some(Duration::from_secs(
+            seconds.parse::<u64>().map_err(|_| usage())?,
+        )),
+    };
+
+    if duration.is_some_and(|duration| duration > MAX_MUTE_DURATION) {
+        return Err(format!(
+            "Mutes can last at most {} seconds, leave them out to mute until unmuted",
+            MAX_MUTE_DURATION.as_secs()
+        ));
+    }
+
+    let target = find_player(world, username)?;
+
+    world.entity_mut(target.entity).insert(Muted::new(duration));
+    send_system_message(world, target.client_id, muted_message(duration));
+
+    Ok(Some(format!("Muted {}", target.username)))
+}
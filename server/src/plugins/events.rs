@@ -0,0 +1,125 @@
+use bevy::{
+    app::{App, Plugin, Update},
+    time::{Time, Timer, TimerMode},
+};
+use bevy_ecs::prelude::*;
+use bevy_quinnet::server::Endpoint;
+use engine::components::{
+    movement::Movement,
+    player::{Player, PlayerPosition},
+};
+use models::server::api::{MovementState, PlayerDetails, PositionSnapshot, ServerEvent};
+use std::time::Duration;
+use tokio::sync::broadcast;
+
+use super::network::PlayerSession;
+
+/// How often subscribers get the position of every player.
+const POSITION_SNAPSHOT_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Publishes [`ServerEvent`]s to everyone subscribed to the management event stream.
+#[derive(Clone, Resource)]
+pub struct ManagementEvents(broadcast::Sender<ServerEvent>);
+
+impl ManagementEvents {
+    pub fn new(capacity: usize) -> Self {
+        let (tx, _) = broadcast::channel(capacity);
+        Self(tx)
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<ServerEvent> {
+        self.0.subscribe()
+    }
+
+    pub fn send(&self, event: ServerEvent) {
+        // Nobody listening is fine, the event is simply dropped
+        let _ = self.0.send(event);
+    }
+}
+
+pub struct EventsPlugin {
+    events: ManagementEvents,
+}
+
+impl EventsPlugin {
+    pub fn new(events: ManagementEvents) -> Self {
+        Self { events }
+    }
+}
+
+impl Plugin for EventsPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(self.events.clone())
+            .insert_resource(PositionSnapshotTimer(Timer::new(
+                POSITION_SNAPSHOT_INTERVAL,
+                TimerMode::Repeating,
+            )))
+            .add_systems(Update, publish_positions);
+    }
+}
+
+#[derive(Resource)]
+struct PositionSnapshotTimer(Timer);
+
+fn publish_positions(
+    players: Query<(&Player, &PlayerPosition)>,
+    events: Res<ManagementEvents>,
+    mut timer: ResMut<PositionSnapshotTimer>,
+    time: Res<Time>,
+) {
+    if !timer.0.tick(time.delta()).just_finished() || events.0.receiver_count() == 0 {
+        return;
+    }
+
+    let players = players
+        .iter()
+        .map(|(player, position)| PositionSnapshot {
+            user_id: player.user_id,
+            position: position.0.to_array(),
+        })
+        .collect();
+
+    events.send(ServerEvent::Positions { players });
+}
+
+/// Publishes an event about the player of `entity` when the queued commands are applied, so it
+/// can be used right before the player is despawned.
+pub fn publish_player_event(
+    commands: &mut Commands,
+    entity: Entity,
+    event: impl FnOnce(&Player) -> ServerEvent + Send + 'static,
+) {
+    commands.add(move |world: &mut World| {
+        let Some(player) = world.get::<Player>(entity) else {
+            return;
+        };
+
+        if let Some(events) = world.get_resource::<ManagementEvents>() {
+            events.send(event(player));
+        }
+    });
+}
+
+pub fn player_details(
+    endpoint: &Endpoint,
+    (player, position, session, movement): (&Player, &PlayerPosition, &PlayerSession, &Movement),
+) -> PlayerDetails {
+    let stats = endpoint.connection_stats(player.client_id);
+
+    PlayerDetails {
+        user_id: player.user_id,
+        username: session.username.clone(),
+        client_id: player.client_id,
+        position: position.0.to_array(),
+        connected_at: session.connected_at,
+        rtt_ms: stats.map(|stats| stats.path.rtt.as_millis() as u64),
+        bytes_sent: stats.map_or(0, |stats| stats.udp_tx.bytes),
+        bytes_received: stats.map_or(0, |stats| stats.udp_rx.bytes),
+        movement: MovementState {
+            forward: movement.forward,
+            backward: movement.backward,
+            left: movement.left,
+            right: movement.right,
+        },
+    }
+}
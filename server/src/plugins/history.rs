@@ -0,0 +1,233 @@
+use anyhow::Result;
+use bevy_ecs::prelude::*;
+use bevy_quinnet::{server::QuinnetServer, shared::ClientId};
+use engine::{
+    components::player::Player,
+    models::network::{ChatChannel, ChatHistoryEntry, ServerMessage},
+};
+use models::server::api::ChatLogEntry;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::VecDeque,
+    fs::{self, OpenOptions},
+    io::Write,
+    path::PathBuf,
+};
+use time::OffsetDateTime;
+use tracing::warn;
+use uuid::Uuid;
+
+use super::chat::PlayerTeam;
+
+/// How many messages a client gets per scrollback request.
+const HISTORY_PAGE: usize = 50;
+
+/// A chat message as kept by the server.
+#[derive(Clone, Deserialize, Serialize)]
+pub struct ChatRecord {
+    /// Absent for messages from the server itself
+    pub user_id: Option<Uuid>,
+    pub username: Option<String>,
+    pub channel: ChatChannel,
+    /// The team of the sender when sent on [`ChatChannel::Team`]
+    pub team: Option<String>,
+    pub message: String,
+    #[serde(with = "time::serde::rfc3339")]
+    pub sent_at: OffsetDateTime,
+}
+
+impl ChatRecord {
+    /// Whether the user, on `team`, could have seen the message.
+    fn visible_to(&self, user_id: Uuid, team: Option<&str>) -> bool {
+        match self.channel {
+            ChatChannel::Global | ChatChannel::System => true,
+            ChatChannel::Team => team.is_some() && self.team.as_deref() == team,
+            ChatChannel::Whisper(target) => target == user_id || self.user_id == Some(user_id),
+        }
+    }
+
+    fn history_entry(&self, seq: u64) -> ChatHistoryEntry {
+        ChatHistoryEntry {
+            seq,
+            user_id: self.user_id,
+            channel: self.channel,
+            message: self.message.clone(),
+            sent_at: self.sent_at,
+        }
+    }
+
+    pub fn log_entry(&self, seq: u64) -> ChatLogEntry {
+        ChatLogEntry {
+            seq,
+            user_id: self.user_id,
+            username: self.username.clone(),
+            channel: self.channel.to_string(),
+            team: self.team.clone(),
+            recipient: match self.channel {
+                ChatChannel::Whisper(user_id) => Some(user_id),
+                _ => None,
+            },
+            message: self.message.clone(),
+            sent_at: self.sent_at,
+        }
+    }
+}
+
+/// The most recent chat of the server, oldest first, appended to a JSON lines file when given
+/// one.
+#[derive(Resource)]
+pub struct ChatLog {
+    records: VecDeque<ChatRecord>,
+    /// The sequence number of the first record, counting every message since the log was loaded
+    first_seq: u64,
+    capacity: usize,
+    path: Option<PathBuf>,
+    /// How many records the file holds, it's compacted once that's twice the capacity
+    lines: usize,
+    /// How many messages players get when they join
+    on_join: usize,
+}
+
+impl ChatLog {
+    pub fn load(path: Option<PathBuf>, capacity: usize, on_join: usize) -> Result<Self> {
+        let mut records = VecDeque::with_capacity(capacity);
+
+        if let Some(path) = path.as_ref().filter(|path| path.exists()) {
+            for line in fs::read_to_string(path)?.lines() {
+                match serde_json::from_str::<ChatRecord>(line) {
+                    Ok(record) => records.push_back(record),
+                    Err(err) => warn!("Skipping unreadable chat log line: {err}"),
+                }
+            }
+
+            while records.len() > capacity {
+                records.pop_front();
+            }
+
+            rewrite(path, &records)?;
+        }
+
+        Ok(Self {
+            lines: records.len(),
+            records,
+            first_seq: 0,
+            capacity,
+            path,
+            on_join,
+        })
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    pub fn push(&mut self, record: ChatRecord) {
+        if self.capacity == 0 {
+            return;
+        }
+
+        if self.records.len() >= self.capacity {
+            self.records.pop_front();
+            self.first_seq += 1;
+        }
+
+        self.records.push_back(record);
+
+        let Some(path) = &self.path else {
+            return;
+        };
+
+        let written = match self.lines + 1 >= self.capacity * 2 {
+            true => rewrite(path, &self.records).map(|_| self.records.len()),
+            false => append(path, self.records.back().unwrap()).map(|_| self.lines + 1),
+        };
+
+        match written {
+            Ok(lines) => self.lines = lines,
+            Err(err) => warn!("Could not write to the chat log: {err}"),
+        }
+    }
+
+    /// The last `limit` records with a sequence number below `before` that pass `filter`, with
+    /// their sequence numbers, oldest first, and whether there are older ones.
+    pub fn page(
+        &self,
+        before: Option<u64>,
+        limit: usize,
+        filter: impl Fn(&ChatRecord) -> bool,
+    ) -> (Vec<(u64, &ChatRecord)>, bool) {
+        let mut older = self
+            .records
+            .iter()
+            .enumerate()
+            .rev()
+            .map(|(index, record)| (self.first_seq + index as u64, record))
+            .filter(|(seq, _)| before.is_none_or(|before| *seq < before))
+            .filter(|(_, record)| filter(record));
+
+        let mut page: Vec<(u64, &ChatRecord)> = older.by_ref().take(limit).collect();
+        page.reverse();
+
+        (page, older.next().is_some())
+    }
+}
+
+/// Replaces the file with only what's kept, so it doesn't grow forever.
+fn rewrite<'a>(path: &PathBuf, records: impl IntoIterator<Item = &'a ChatRecord>) -> Result<()> {
+    let mut contents = String::new();
+    for record in records {
+        contents.push_str(&serde_json::to_string(record)?);
+        contents.push('\n');
+    }
+
+    let tmp = path.with_extension("tmp");
+    fs::write(&tmp, contents)?;
+    fs::rename(tmp, path)?;
+
+    Ok(())
+}
+
+fn append(path: &PathBuf, record: &ChatRecord) -> Result<()> {
+    let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+    writeln!(file, "{}", serde_json::to_string(record)?)?;
+
+    Ok(())
+}
+
+/// Sends the player of `client_id` the messages it can see from before the sequence number
+/// `before`, the most recent ones it gets on joining when absent.
+pub fn send_chat_history(world: &mut World, client_id: ClientId, before: Option<u64>) {
+    let Some((entity, user_id)) = world
+        .query::<(Entity, &Player)>()
+        .iter(world)
+        .find(|(_, player)| player.client_id == client_id)
+        .map(|(entity, player)| (entity, player.user_id))
+    else {
+        return;
+    };
+
+    let team = world.get::<PlayerTeam>(entity).map(|team| team.0.clone());
+
+    let log = world.resource::<ChatLog>();
+    let limit = match before {
+        Some(_) => HISTORY_PAGE,
+        None => log.on_join,
+    };
+
+    let (page, more) = log.page(before, limit, |record| {
+        record.visible_to(user_id, team.as_deref())
+    });
+
+    let message = ServerMessage::ChatHistory {
+        messages: page
+            .into_iter()
+            .map(|(seq, record)| record.history_entry(seq))
+            .collect(),
+        more,
+    };
+
+    world
+        .resource::<QuinnetServer>()
+        .endpoint()
+        .try_send_message(client_id, message);
+}
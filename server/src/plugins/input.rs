@@ -0,0 +1,57 @@
+use bevy::app::{App, FixedUpdate, Plugin};
+use bevy_ecs::prelude::*;
+use engine::{
+    components::movement::Movement, models::network::PlayerInput, plugins::movement::MovementSet,
+};
+use std::collections::VecDeque;
+
+/// Inputs a player can be ahead of the server, older ones are dropped past it.
+const MAX_QUEUED_INPUTS: usize = 8;
+
+/// The inputs of a player waiting for a tick, applied one per tick so sending more doesn't
+/// move a player faster.
+#[derive(Default, Component)]
+pub struct InputQueue {
+    pending: VecDeque<PlayerInput>,
+    /// The sequence of the last input applied to the player
+    pub last_applied: u32,
+}
+
+impl InputQueue {
+    /// Queues `input` unless it's older than what was applied or queued already.
+    pub fn push(&mut self, input: PlayerInput) {
+        let newest = self
+            .pending
+            .back()
+            .map_or(self.last_applied, |input| input.sequence);
+
+        if input.sequence <= newest {
+            return;
+        }
+
+        if self.pending.len() >= MAX_QUEUED_INPUTS {
+            self.pending.pop_front();
+        }
+
+        self.pending.push_back(input);
+    }
+}
+
+pub struct InputPlugin;
+
+impl Plugin for InputPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(FixedUpdate, apply_inputs.before(MovementSet));
+    }
+}
+
+/// Sets the movement of every player to its next input, players without one keep moving as
+/// they were.
+fn apply_inputs(mut players: Query<(&mut InputQueue, &mut Movement)>) {
+    for (mut queue, mut movement) in players.iter_mut() {
+        if let Some(input) = queue.pending.pop_front() {
+            *movement = input.movement;
+            queue.last_applied = input.sequence;
+        }
+    }
+}
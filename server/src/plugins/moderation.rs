@@ -0,0 +1,300 @@
+use bevy::app::{App, Plugin};
+use bevy_ecs::prelude::*;
+use bevy_quinnet::shared::ClientId;
+use models::server::api::ChatStats;
+use std::time::{Duration, Instant};
+use time::OffsetDateTime;
+
+use super::{
+    chat::send_system_message,
+    network::{muted_message, Muted},
+};
+
+/// Violations older than this are forgotten.
+const VIOLATION_WINDOW: Duration = Duration::from_secs(60);
+
+/// How much players can chat and what happens when they overdo it.
+#[derive(Clone, Resource)]
+pub struct ChatConfig {
+    /// Messages a player can send in a row before being limited
+    pub burst: u32,
+    /// Messages per second the allowance of a player refills at
+    pub rate: f32,
+    /// The most characters a message can have
+    pub max_length: usize,
+    /// Violations in a row after which a player is muted automatically
+    pub max_violations: u32,
+    pub auto_mute: Duration,
+}
+
+/// What a [`ChatFilter`] decided about a message.
+pub enum FilterOutcome {
+    Allow,
+    /// Sends this instead, which counts as a violation
+    Replace(String),
+    /// Drops the message with the reason told to the sender, which counts as a violation
+    Block(String),
+}
+
+/// A step of the filter pipeline every player message goes through before it's delivered.
+pub trait ChatFilter: Send + Sync {
+    fn filter(&self, message: &str) -> FilterOutcome;
+}
+
+/// The filters run on player messages, in order, other plugins add theirs with
+/// [`ChatFiltersExt`].
+#[derive(Default, Resource)]
+pub struct ChatFilters(Vec<Box<dyn ChatFilter>>);
+
+pub trait ChatFiltersExt {
+    fn add_chat_filter(&mut self, filter: impl ChatFilter + 'static) -> &mut Self;
+}
+
+impl ChatFiltersExt for App {
+    fn add_chat_filter(&mut self, filter: impl ChatFilter + 'static) -> &mut Self {
+        self.world_mut()
+            .get_resource_or_insert_with(ChatFilters::default)
+            .0
+            .push(Box::new(filter));
+        self
+    }
+}
+
+/// Masks every word of a word list, ignoring case.
+pub struct WordFilter {
+    words: Vec<String>,
+}
+
+impl WordFilter {
+    pub fn new(words: impl IntoIterator<Item = String>) -> Self {
+        Self {
+            words: words
+                .into_iter()
+                .map(|word| word.trim().to_lowercase())
+                .filter(|word| !word.is_empty())
+                .collect(),
+        }
+    }
+}
+
+impl ChatFilter for WordFilter {
+    fn filter(&self, message: &str) -> FilterOutcome {
+        let mut masked = false;
+
+        let filtered: String = message
+            .split_inclusive(|c: char| !c.is_alphanumeric())
+            .map(|part| {
+                let word = part.trim_end_matches(|c: char| !c.is_alphanumeric());
+
+                match self.words.contains(&word.to_lowercase()) {
+                    true => {
+                        masked = true;
+                        "*".repeat(word.chars().count()) + &part[word.len()..]
+                    }
+                    false => part.to_string(),
+                }
+            })
+            .collect();
+
+        match masked {
+            true => FilterOutcome::Replace(filtered),
+            false => FilterOutcome::Allow,
+        }
+    }
+}
+
+/// Blocks messages with links in them.
+pub struct LinkFilter;
+
+impl LinkFilter {
+    fn is_link(word: &str) -> bool {
+        let word = word.trim_matches(|c: char| !c.is_alphanumeric());
+
+        if word.contains("://") || word.starts_with("www.") {
+            return true;
+        }
+
+        // Bare domains like example.com, but not numbers or abbreviations
+        let parts: Vec<&str> = word.split('.').collect();
+        parts.len() >= 2
+            && parts.iter().all(|part| {
+                !part.is_empty() && part.chars().all(|c| c.is_alphanumeric() || c == '-')
+            })
+            && parts.last().is_some_and(|tld| {
+                (2..=6).contains(&tld.len()) && tld.chars().all(|c| c.is_ascii_lowercase())
+            })
+    }
+}
+
+impl ChatFilter for LinkFilter {
+    fn filter(&self, message: &str) -> FilterOutcome {
+        match message.split_whitespace().any(LinkFilter::is_link) {
+            true => FilterOutcome::Block(String::from("Links are not allowed in chat")),
+            false => FilterOutcome::Allow,
+        }
+    }
+}
+
+/// The chat allowance of a player, as a token bucket.
+#[derive(Component)]
+struct ChatLimiter {
+    tokens: f32,
+    refilled_at: Instant,
+    violations: u32,
+    violated_at: Instant,
+}
+
+/// What moderation did to the chat since the server started.
+#[derive(Default, Resource)]
+pub struct ChatCounters(pub ChatStats);
+
+pub struct ModerationPlugin {
+    config: ChatConfig,
+}
+
+impl ModerationPlugin {
+    pub fn new(config: ChatConfig) -> Self {
+        Self { config }
+    }
+}
+
+impl Plugin for ModerationPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(self.config.clone())
+            .init_resource::<ChatFilters>()
+            .init_resource::<ChatCounters>();
+    }
+}
+
+/// Takes a message from the allowance of the player, telling it to slow down when it ran out.
+pub fn take_chat_token(world: &mut World, entity: Entity, client_id: ClientId) -> bool {
+    let config = world.resource::<ChatConfig>().clone();
+    let now = Instant::now();
+
+    if world.get::<ChatLimiter>(entity).is_none() {
+        world.entity_mut(entity).insert(ChatLimiter {
+            tokens: config.burst as f32,
+            refilled_at: now,
+            violations: 0,
+            violated_at: now,
+        });
+    }
+
+    let mut limiter = world.get_mut::<ChatLimiter>(entity).unwrap();
+
+    let elapsed = now.duration_since(limiter.refilled_at).as_secs_f32();
+    limiter.tokens = (limiter.tokens + elapsed * config.rate).min(config.burst as f32);
+    limiter.refilled_at = now;
+
+    let allowed = limiter.tokens >= 1.0;
+    if allowed {
+        limiter.tokens -= 1.0;
+    }
+
+    if !allowed {
+        world.resource_mut::<ChatCounters>().0.rate_limited += 1;
+        send_system_message(world, client_id, "You are sending messages too fast");
+        record_violation(world, entity, client_id);
+    }
+
+    allowed
+}
+
+/// Checks the length of a message and runs it through the filters, returning what to send.
+pub fn moderate_message(
+    world: &mut World,
+    entity: Entity,
+    client_id: ClientId,
+    message: String,
+) -> Result<String, String> {
+    let max_length = world.resource::<ChatConfig>().max_length;
+
+    if message.trim().is_empty() {
+        return Err(String::from("Messages can't be empty"));
+    }
+
+    if message.chars().count() > max_length {
+        world.resource_mut::<ChatCounters>().0.too_long += 1;
+        record_violation(world, entity, client_id);
+        return Err(format!(
+            "Messages can be at most {max_length} characters long"
+        ));
+    }
+
+    let mut message = message;
+    let mut violated = false;
+    let mut blocked = None;
+
+    for filter in world.resource::<ChatFilters>().0.iter() {
+        match filter.filter(&message) {
+            FilterOutcome::Allow => {}
+            FilterOutcome::Replace(replacement) => {
+                message = replacement;
+                violated = true;
+            }
+            FilterOutcome::Block(reason) => {
+                blocked = Some(reason);
+                break;
+            }
+        }
+    }
+
+    let mut counters = world.resource_mut::<ChatCounters>();
+    match blocked {
+        Some(_) => counters.0.blocked += 1,
+        None => {
+            counters.0.messages += 1;
+
+            if violated {
+                counters.0.masked += 1;
+            }
+        }
+    }
+
+    if blocked.is_some() || violated {
+        record_violation(world, entity, client_id);
+    }
+
+    match blocked {
+        Some(reason) => Err(reason),
+        None => Ok(message),
+    }
+}
+
+/// Counts a violation of the player, muting it once it had too many in a row.
+fn record_violation(world: &mut World, entity: Entity, client_id: ClientId) {
+    let config = world.resource::<ChatConfig>().clone();
+    let now = Instant::now();
+
+    let Some(mut limiter) = world.get_mut::<ChatLimiter>(entity) else {
+        return;
+    };
+
+    if now.duration_since(limiter.violated_at) > VIOLATION_WINDOW {
+        limiter.violations = 0;
+    }
+
+    limiter.violations += 1;
+    limiter.violated_at = now;
+
+    if limiter.violations < config.max_violations {
+        return;
+    }
+
+    limiter.violations = 0;
+
+    // A mute by an operator, which may last longer, is left alone.
+    if world
+        .get::<Muted>(entity)
+        .is_some_and(|muted| muted.is_active(OffsetDateTime::now_utc()))
+    {
+        return;
+    }
+
+    world
+        .entity_mut(entity)
+        .insert(Muted::new(Some(config.auto_mute)));
+    world.resource_mut::<ChatCounters>().0.auto_mutes += 1;
+
+    send_system_message(world, client_id, muted_message(Some(config.auto_mute)));
+}
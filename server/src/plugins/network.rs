@@ -1,40 +1,120 @@
 use bevy::{
-    app::{App, Plugin, Startup, Update},
-    time::{Time, Timer, TimerMode},
+    app::{App, FixedUpdate, Plugin, Startup, Update},
+    log::{info, warn},
+    math::Vec3,
+    time::Time,
 };
 use bevy_ecs::prelude::*;
 use bevy_quinnet::{
     server::{
-        certificate::CertificateRetrievalMode, QuinnetServer, QuinnetServerPlugin,
-        ServerEndpointConfiguration,
+        certificate::CertificateRetrievalMode, ConnectionEvent, ConnectionLostEvent, Endpoint,
+        QuinnetServer, QuinnetServerPlugin, ServerEndpointConfiguration,
     },
-    shared::channels::ChannelsConfiguration,
+    shared::{channels::ChannelsConfiguration, ClientId},
 };
 use engine::{
     components::{
         movement::Movement,
-        player::{Player, PlayerPosition},
+        player::{Player, PlayerPosition, PlayerRole},
     },
-    models::network::{ClientMessage, ServerMessage},
+    models::network::{ClientMessage, KickReason, ServerMessage},
+    plugins::movement::{MovementSet, Tick},
+    tokens::{JoinClaims, TokenVerifier},
 };
+use models::server::api::ServerEvent;
 use std::{
+    collections::HashMap,
     net::{IpAddr, Ipv4Addr},
     time::Duration,
 };
+use time::OffsetDateTime;
+use uuid::Uuid;
+
+use crate::{access::AccessList, AppState};
+
+use super::{
+    chat::handle_chat_message,
+    events::{player_details, publish_player_event, ManagementEvents},
+    history::send_chat_history,
+    input::InputQueue,
+};
 
 #[derive(Resource)]
 pub struct ServerConfig {
     port: u16,
-    broadcast_timer: Timer,
+    ticket_verifier: TokenVerifier,
+    idle_timeout: Duration,
+    max_players: usize,
+}
+
+/// Who a player joined as and when.
+#[derive(Component)]
+pub struct PlayerSession {
+    pub username: String,
+    pub connected_at: OffsetDateTime,
+}
+
+/// Keeps the chat messages of a player from reaching anyone.
+#[derive(Component)]
+pub struct Muted {
+    /// Muted until unmuted or disconnected when absent
+    pub until: Option<OffsetDateTime>,
+}
+
+/// The longest a player can be muted for, longer mutes have no end.
+pub const MAX_MUTE_DURATION: Duration = Duration::from_secs(365 * 24 * 60 * 60);
+
+impl Muted {
+    /// Mutes for `duration`, which is at most [`MAX_MUTE_DURATION`].
+    pub fn new(duration: Option<Duration>) -> Self {
+        Self {
+            until: duration
+                .map(|duration| OffsetDateTime::now_utc() + duration.min(MAX_MUTE_DURATION)),
+        }
+    }
+
+    pub fn is_active(&self, now: OffsetDateTime) -> bool {
+        self.until.is_none_or(|until| until > now)
+    }
+}
+
+/// Whether a player can be put at `position`, which only holds when every coordinate is finite.
+pub fn is_valid_position(position: Vec3) -> bool {
+    position.is_finite()
+}
+
+/// What a player is told when it gets muted.
+pub fn muted_message(duration: Option<Duration>) -> String {
+    match duration {
+        Some(duration) => format!("You were muted for {} seconds", duration.as_secs()),
+        None => String::from("You were muted"),
+    }
 }
 
+/// When each connected client last sent a message, in [`Time::elapsed`].
+#[derive(Default, Resource)]
+struct ClientActivity(HashMap<ClientId, Duration>);
+
 pub struct NetworkPlugin {
     port: u16,
+    ticket_verifier: TokenVerifier,
+    idle_timeout: Duration,
+    max_players: usize,
 }
 
 impl NetworkPlugin {
-    pub fn new(port: u16) -> Self {
-        Self { port }
+    pub fn new(
+        port: u16,
+        ticket_verifier: TokenVerifier,
+        idle_timeout: Duration,
+        max_players: usize,
+    ) -> Self {
+        Self {
+            port,
+            ticket_verifier,
+            idle_timeout,
+            max_players,
+        }
     }
 }
 
@@ -42,12 +122,17 @@ impl Plugin for NetworkPlugin {
     fn build(&self, app: &mut App) {
         app.insert_resource(ServerConfig {
             port: self.port,
-            broadcast_timer: Timer::new(Duration::from_millis(10), TimerMode::Repeating),
+            ticket_verifier: self.ticket_verifier.clone(),
+            idle_timeout: self.idle_timeout,
+            max_players: self.max_players,
         })
+        .init_resource::<ClientActivity>()
         .add_plugins(QuinnetServerPlugin::default())
         .add_systems(Startup, start_listening)
+        .add_systems(Update, handle_connection_events)
         .add_systems(Update, handle_client_messages)
-        .add_systems(Update, broadcast_positions);
+        .add_systems(Update, disconnect_idle_clients)
+        .add_systems(FixedUpdate, broadcast_positions.after(MovementSet));
     }
 }
 
@@ -66,29 +151,211 @@ fn start_listening(server_config: Res<ServerConfig>, mut server: ResMut<QuinnetS
         .unwrap();
 }
 
+fn handle_connection_events(
+    players: Query<(Entity, &Player)>,
+    mut commands: Commands,
+    mut server: ResMut<QuinnetServer>,
+    mut connection_events: EventReader<ConnectionEvent>,
+    mut connection_lost_events: EventReader<ConnectionLostEvent>,
+    mut activity: ResMut<ClientActivity>,
+    time: Res<Time>,
+) {
+    for event in connection_events.read() {
+        activity.0.insert(event.id, time.elapsed());
+    }
+
+    for event in connection_lost_events.read() {
+        activity.0.remove(&event.id);
+
+        let entity = players
+            .iter()
+            .find(|(_, player)| player.client_id == event.id)
+            .map(|(entity, _)| entity);
+
+        remove_player(&mut commands, server.endpoint_mut(), entity, event.id);
+    }
+}
+
+fn disconnect_idle_clients(
+    players: Query<(Entity, &Player)>,
+    mut commands: Commands,
+    mut server: ResMut<QuinnetServer>,
+    mut activity: ResMut<ClientActivity>,
+    server_config: Res<ServerConfig>,
+    time: Res<Time>,
+) {
+    let now = time.elapsed();
+    let endpoint = server.endpoint_mut();
+    let clients = endpoint.clients();
+
+    activity
+        .0
+        .retain(|client_id, _| clients.contains(client_id));
+
+    let idle: Vec<ClientId> = activity
+        .0
+        .iter()
+        .filter(|(_, last_seen)| now.saturating_sub(**last_seen) > server_config.idle_timeout)
+        .map(|(client_id, _)| *client_id)
+        .collect();
+
+    for client_id in idle {
+        activity.0.remove(&client_id);
+
+        let entity = players
+            .iter()
+            .find(|(_, player)| player.client_id == client_id)
+            .map(|(entity, _)| entity);
+
+        kick_client(&mut commands, endpoint, entity, client_id, KickReason::Idle);
+    }
+}
+
+/// Tells the client why it is being removed, removes its player and closes the connection.
+pub fn kick_client(
+    commands: &mut Commands,
+    endpoint: &mut Endpoint,
+    entity: Option<Entity>,
+    client_id: ClientId,
+    reason: KickReason,
+) {
+    info!("Kicking client {client_id}: {reason}");
+
+    if let Some(entity) = entity {
+        let reason = reason.to_string();
+        publish_player_event(commands, entity, move |player| ServerEvent::PlayerKicked {
+            user_id: player.user_id,
+            reason,
+        });
+    }
+
+    endpoint.try_send_message(client_id, ServerMessage::Kicked { reason });
+    remove_player(commands, endpoint, entity, client_id);
+    endpoint.try_disconnect_client(client_id);
+}
+
+/// Despawns the player of `client_id`, if it joined, and lets everyone know it left.
+pub fn remove_player(
+    commands: &mut Commands,
+    endpoint: &mut Endpoint,
+    entity: Option<Entity>,
+    client_id: ClientId,
+) {
+    if let Some(entity) = entity {
+        publish_player_event(commands, entity, |player| ServerEvent::PlayerLeft {
+            user_id: player.user_id,
+        });
+        commands.entity(entity).despawn();
+        endpoint
+            .broadcast_message(ServerMessage::ClientDisconnected { client_id })
+            .unwrap();
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
 fn handle_client_messages(
-    mut players: Query<(Entity, &Player, &mut PlayerPosition, &mut Movement)>,
+    mut players: Query<(Entity, &Player, &mut InputQueue)>,
+    events: Res<ManagementEvents>,
     mut commands: Commands,
     mut server: ResMut<QuinnetServer>,
+    mut activity: ResMut<ClientActivity>,
+    server_config: Res<ServerConfig>,
+    state: Res<AppState>,
+    access_list: Res<AccessList>,
+    time: Res<Time>,
 ) {
     let endpoint = server.endpoint_mut();
+
+    // Players spawned this frame aren't in the query yet, so joins are tracked here too.
+    let mut joined: Vec<(ClientId, Uuid)> = players
+        .iter()
+        .map(|(_, player, _)| (player.client_id, player.user_id))
+        .collect();
+
     for client_id in endpoint.clients() {
         while let Some((_channel_id, message)) =
             endpoint.try_receive_message_from::<ClientMessage>(client_id)
         {
+            activity.0.insert(client_id, time.elapsed());
+
             match message {
-                ClientMessage::Join { user_id } => {
+                ClientMessage::Join { ticket } => {
+                    if joined.iter().any(|(joined, _)| *joined == client_id) {
+                        warn!("Client {client_id} tried to join again");
+                        continue;
+                    }
+
+                    let claims =
+                        verify_join_ticket(&server_config, &state, &ticket).and_then(|claims| {
+                            match joined.iter().any(|(_, user_id)| *user_id == claims.user_id) {
+                                true => Err(String::from("You are already playing on this server")),
+                                false => Ok(claims),
+                            }
+                        });
+
+                    let claims = match claims {
+                        Ok(claims) => claims,
+                        Err(reason) => {
+                            endpoint
+                                .send_message(client_id, ServerMessage::JoinRejected { reason })
+                                .unwrap();
+                            endpoint.disconnect_client(client_id).unwrap();
+                            break;
+                        }
+                    };
+
+                    let user_id = claims.user_id;
+
+                    if let Err(reason) = access_list.check(user_id) {
+                        activity.0.remove(&client_id);
+                        kick_client(&mut commands, endpoint, None, client_id, reason);
+                        break;
+                    }
+
+                    if joined.len() >= server_config.max_players {
+                        activity.0.remove(&client_id);
+                        kick_client(
+                            &mut commands,
+                            endpoint,
+                            None,
+                            client_id,
+                            KickReason::ServerFull,
+                        );
+                        break;
+                    }
+
+                    joined.push((client_id, user_id));
+
+                    let player = Player { client_id, user_id };
+                    let session = PlayerSession {
+                        username: claims.username,
+                        connected_at: OffsetDateTime::now_utc(),
+                    };
+                    let position = PlayerPosition::default();
+                    let movement = Movement::default();
+
+                    events.send(ServerEvent::PlayerJoined {
+                        player: player_details(endpoint, (&player, &position, &session, &movement)),
+                    });
+
                     commands.spawn((
-                        Player { client_id, user_id },
-                        PlayerPosition::default(),
-                        Movement::default(),
+                        player,
+                        PlayerRole(claims.role),
+                        session,
+                        position,
+                        movement,
+                        InputQueue::default(),
                     ));
 
                     endpoint
                         .broadcast_message(ServerMessage::ClientConnected { client_id, user_id })
                         .unwrap();
 
-                    for (_, player, _, _) in players.into_iter() {
+                    commands.add(move |world: &mut World| {
+                        send_chat_history(world, client_id, None);
+                    });
+
+                    for (_, player, _) in players.iter() {
                         endpoint
                             .send_message(
                                 client_id,
@@ -100,52 +367,34 @@ fn handle_client_messages(
                             .unwrap();
                     }
                 }
-                ClientMessage::Disconnect {} => {
-                    if let Some((entity, _, _, _)) = players
+                ClientMessage::Disconnect => {
+                    let entity = players
                         .iter()
-                        .find(|(_, player, _, _)| player.client_id == client_id)
-                    {
-                        commands.entity(entity).despawn();
-                        endpoint
-                            .broadcast_message(ServerMessage::ClientDisconnected { client_id })
-                            .unwrap();
+                        .find(|(_, player, _)| player.client_id == client_id)
+                        .map(|(entity, _, _)| entity);
 
-                        endpoint.disconnect_client(client_id).unwrap();
-                    }
+                    remove_player(&mut commands, endpoint, entity, client_id);
+                    activity.0.remove(&client_id);
+                    endpoint.disconnect_client(client_id).unwrap();
+                    break;
                 }
-                ClientMessage::ChatMessage { message } => {
-                    endpoint
-                        .broadcast_message(ServerMessage::ChatMessage { client_id, message })
-                        .unwrap();
+                ClientMessage::Heartbeat => {}
+                ClientMessage::ChatMessage { channel, message } => {
+                    commands.add(move |world: &mut World| {
+                        handle_chat_message(world, client_id, channel, message);
+                    });
                 }
-                ClientMessage::UpdatePosition { position } => {
-                    if let Some((_, _, mut player_position, _)) = players
-                        .iter_mut()
-                        .find(|(_, player, _, _)| player.client_id == client_id)
-                    {
-                        player_position.0 = position;
-
-                        endpoint
-                            .broadcast_message(ServerMessage::UpdatePosition {
-                                client_id,
-                                position,
-                            })
-                            .unwrap();
-                    }
+                ClientMessage::RequestChatHistory { before } => {
+                    commands.add(move |world: &mut World| {
+                        send_chat_history(world, client_id, Some(before));
+                    });
                 }
-                ClientMessage::SendModifier(modifier) => {
-                    if let Some((_, _, _, mut movement)) = players
+                ClientMessage::Input(input) => {
+                    if let Some((_, _, mut queue)) = players
                         .iter_mut()
-                        .find(|(_, player, _, _)| player.client_id == client_id)
+                        .find(|(_, player, _)| player.client_id == client_id)
                     {
-                        movement.modify(modifier.clone());
-
-                        endpoint
-                            .broadcast_message(ServerMessage::SendModifier {
-                                client_id,
-                                modifier,
-                            })
-                            .unwrap();
+                        queue.push(input);
                     }
                 }
             }
@@ -153,22 +402,37 @@ fn handle_client_messages(
     }
 }
 
+fn verify_join_ticket(
+    server_config: &ServerConfig,
+    state: &AppState,
+    ticket: &str,
+) -> Result<JoinClaims, String> {
+    let server = state
+        .server
+        .as_ref()
+        .ok_or("The server is not registered yet")?;
+
+    server_config
+        .ticket_verifier
+        .verify_join_ticket(ticket, &server.id)
+        .map_err(|_| String::from("Invalid join ticket"))
+}
+
+/// Sends every client where each player ended up after the tick.
 fn broadcast_positions(
-    players: Query<(&Player, &PlayerPosition)>,
+    players: Query<(&Player, &PlayerPosition, &InputQueue)>,
     mut server: ResMut<QuinnetServer>,
-    time: Res<Time>,
-    mut config: ResMut<ServerConfig>,
+    tick: Res<Tick>,
 ) {
-    config.broadcast_timer.tick(time.delta());
-    if config.broadcast_timer.finished() {
-        let endpoint = server.endpoint_mut();
-        for (player, position) in players.iter() {
-            endpoint
-                .broadcast_message(ServerMessage::UpdatePosition {
-                    client_id: player.client_id,
-                    position: position.0,
-                })
-                .unwrap()
-        }
+    let endpoint = server.endpoint_mut();
+    for (player, position, queue) in players.iter() {
+        endpoint
+            .broadcast_message(ServerMessage::UpdatePosition {
+                client_id: player.client_id,
+                position: position.0,
+                input_sequence: queue.last_applied,
+                tick: tick.0,
+            })
+            .unwrap()
     }
 }
@@ -1,18 +1,24 @@
 use anyhow::Result;
-use models::server::api::{PlayerResponse, ServerInfoResponse};
-use reqwest::{Client, Method};
+use models::server::api::{
+    Ban, Broadcast, ChatLogEntry, ChatLogQuery, ChatStats, Mute, NewBan, PlayerDetails,
+    PlayerResponse, RenameServer, ServerEvent, ServerInfoResponse, SetWhitelistEnabled, Teleport,
+    Whitelist,
+};
+use reqwest::{Client, Method, RequestBuilder, Response};
 use std::time::Duration;
 use uuid::Uuid;
 
-pub struct ServerApiClient<'a> {
-    base_url: &'a str,
+pub struct ServerApiClient {
+    base_url: String,
+    admin_token: String,
     client: Client,
 }
 
-impl<'a> ServerApiClient<'a> {
-    pub fn new(base_url: &'a str) -> Self {
+impl ServerApiClient {
+    pub fn new(base_url: impl Into<String>, admin_token: impl Into<String>) -> Self {
         Self {
-            base_url,
+            base_url: base_url.into(),
+            admin_token: admin_token.into(),
             client: Client::builder()
                 .connect_timeout(Duration::from_secs(30))
                 .build()
@@ -20,36 +26,278 @@ impl<'a> ServerApiClient<'a> {
         }
     }
 
+    fn request(&self, method: Method, path: &str) -> RequestBuilder {
+        self.client
+            .request(method, format!("{}{}", self.base_url, path))
+            .bearer_auth(&self.admin_token)
+    }
+
     pub async fn get_server_info(&self) -> Result<ServerInfoResponse> {
         let response = self
-            .client
-            .request(Method::GET, format!("{}/", self.base_url))
+            .request(Method::GET, "/")
             .send()
-            .await?;
+            .await?
+            .error_for_status()?;
 
         let auth_response = response.json::<ServerInfoResponse>().await?;
 
         Ok(auth_response)
     }
 
+    /// Subscribes to the events the server publishes as they happen.
+    pub async fn subscribe(&self) -> Result<EventSubscription> {
+        let response = self
+            .request(Method::GET, "/events")
+            .send()
+            .await?
+            .error_for_status()?;
+
+        Ok(EventSubscription {
+            response,
+            buffer: Vec::new(),
+        })
+    }
+
     pub async fn get_players(&self) -> Result<PlayerResponse> {
         let response = self
-            .client
-            .request(Method::GET, format!("{}/players", self.base_url))
+            .request(Method::GET, "/players")
             .send()
-            .await?;
+            .await?
+            .error_for_status()?;
 
         let auth_response = response.json::<PlayerResponse>().await?;
 
         Ok(auth_response)
     }
 
-    pub async fn kick_player(&self, id: Uuid) -> Result<()> {
-        self.client
-            .request(Method::DELETE, format!("{}/players/{}", self.base_url, id))
+    pub async fn get_player(&self, id: Uuid) -> Result<PlayerDetails> {
+        let response = self
+            .request(Method::GET, &format!("/players/{}", id))
+            .send()
+            .await?
+            .error_for_status()?;
+
+        Ok(response.json::<PlayerDetails>().await?)
+    }
+
+    pub async fn kick_player(&self, id: Uuid, reason: Option<&str>) -> Result<()> {
+        self.request(Method::DELETE, &format!("/players/{}", id))
+            .query(&[("reason", reason)])
+            .send()
+            .await?
+            .error_for_status()?;
+
+        Ok(())
+    }
+
+    pub async fn rename_server(&self, name: &str) -> Result<ServerInfoResponse> {
+        let response = self
+            .request(Method::PATCH, "/")
+            .json(&RenameServer {
+                name: name.to_string(),
+            })
+            .send()
+            .await?
+            .error_for_status()?;
+
+        Ok(response.json::<ServerInfoResponse>().await?)
+    }
+
+    pub async fn broadcast(&self, message: &str) -> Result<()> {
+        self.request(Method::POST, "/broadcast")
+            .json(&Broadcast {
+                message: message.to_string(),
+            })
+            .send()
+            .await?
+            .error_for_status()?;
+
+        Ok(())
+    }
+
+    pub async fn teleport_player(&self, id: Uuid, position: [f32; 3]) -> Result<()> {
+        self.request(Method::POST, &format!("/players/{}/teleport", id))
+            .json(&Teleport { position })
+            .send()
+            .await?
+            .error_for_status()?;
+
+        Ok(())
+    }
+
+    pub async fn mute_player(&self, id: Uuid, duration_secs: Option<u64>) -> Result<()> {
+        self.request(Method::POST, &format!("/players/{}/mute", id))
+            .json(&Mute { duration_secs })
+            .send()
+            .await?
+            .error_for_status()?;
+
+        Ok(())
+    }
+
+    pub async fn unmute_player(&self, id: Uuid) -> Result<()> {
+        self.request(Method::DELETE, &format!("/players/{}/mute", id))
+            .send()
+            .await?
+            .error_for_status()?;
+
+        Ok(())
+    }
+
+    pub async fn list_bans(&self) -> Result<Vec<Ban>> {
+        let response = self
+            .request(Method::GET, "/bans")
+            .send()
+            .await?
+            .error_for_status()?;
+
+        Ok(response.json::<Vec<Ban>>().await?)
+    }
+
+    pub async fn ban_player(&self, new_ban: &NewBan) -> Result<Ban> {
+        let response = self
+            .request(Method::POST, "/bans")
+            .json(new_ban)
+            .send()
+            .await?
+            .error_for_status()?;
+
+        Ok(response.json::<Ban>().await?)
+    }
+
+    pub async fn unban_player(&self, id: Uuid) -> Result<()> {
+        self.request(Method::DELETE, &format!("/bans/{}", id))
             .send()
-            .await?;
+            .await?
+            .error_for_status()?;
 
         Ok(())
     }
+
+    pub async fn get_whitelist(&self) -> Result<Whitelist> {
+        let response = self
+            .request(Method::GET, "/whitelist")
+            .send()
+            .await?
+            .error_for_status()?;
+
+        Ok(response.json::<Whitelist>().await?)
+    }
+
+    pub async fn set_whitelist_enabled(&self, enabled: bool) -> Result<()> {
+        self.request(Method::PATCH, "/whitelist")
+            .json(&SetWhitelistEnabled { enabled })
+            .send()
+            .await?
+            .error_for_status()?;
+
+        Ok(())
+    }
+
+    pub async fn add_to_whitelist(&self, id: Uuid) -> Result<()> {
+        self.request(Method::PUT, &format!("/whitelist/{}", id))
+            .send()
+            .await?
+            .error_for_status()?;
+
+        Ok(())
+    }
+
+    pub async fn remove_from_whitelist(&self, id: Uuid) -> Result<()> {
+        self.request(Method::DELETE, &format!("/whitelist/{}", id))
+            .send()
+            .await?
+            .error_for_status()?;
+
+        Ok(())
+    }
+
+    /// The chat log of the server, oldest first, with at most `limit` messages before the one
+    /// with the sequence number `before`.
+    pub async fn get_chat_log(
+        &self,
+        before: Option<u64>,
+        limit: Option<usize>,
+    ) -> Result<Vec<ChatLogEntry>> {
+        let response = self
+            .request(Method::GET, "/chat/log")
+            .query(&ChatLogQuery { before, limit })
+            .send()
+            .await?
+            .error_for_status()?;
+
+        Ok(response.json::<Vec<ChatLogEntry>>().await?)
+    }
+
+    pub async fn get_chat_stats(&self) -> Result<ChatStats> {
+        let response = self
+            .request(Method::GET, "/chat/stats")
+            .send()
+            .await?
+            .error_for_status()?;
+
+        Ok(response.json::<ChatStats>().await?)
+    }
+
+    pub async fn list_ops(&self) -> Result<Vec<Uuid>> {
+        let response = self
+            .request(Method::GET, "/ops")
+            .send()
+            .await?
+            .error_for_status()?;
+
+        Ok(response.json::<Vec<Uuid>>().await?)
+    }
+
+    pub async fn add_op(&self, id: Uuid) -> Result<()> {
+        self.request(Method::PUT, &format!("/ops/{}", id))
+            .send()
+            .await?
+            .error_for_status()?;
+
+        Ok(())
+    }
+
+    pub async fn remove_op(&self, id: Uuid) -> Result<()> {
+        self.request(Method::DELETE, &format!("/ops/{}", id))
+            .send()
+            .await?
+            .error_for_status()?;
+
+        Ok(())
+    }
+}
+
+/// A stream of server-sent events from the management web server.
+pub struct EventSubscription {
+    response: Response,
+    buffer: Vec<u8>,
+}
+
+impl EventSubscription {
+    /// Waits for the next event, `None` once the server closes the stream.
+    pub async fn next(&mut self) -> Result<Option<ServerEvent>> {
+        loop {
+            while let Some(end) = self.buffer.windows(2).position(|window| window == b"\n\n") {
+                let message: Vec<u8> = self.buffer.drain(..end + 2).collect();
+                let message = std::str::from_utf8(&message)?;
+
+                let data: Vec<&str> = message
+                    .lines()
+                    .filter_map(|line| line.strip_prefix("data:"))
+                    .map(|data| data.strip_prefix(' ').unwrap_or(data))
+                    .collect();
+
+                // Keep-alive comments carry no data
+                if !data.is_empty() {
+                    return Ok(Some(serde_json::from_str(&data.join("\n"))?));
+                }
+            }
+
+            match self.response.chunk().await? {
+                Some(chunk) => self.buffer.extend_from_slice(&chunk),
+                None => return Ok(None),
+            }
+        }
+    }
 }
@@ -1,20 +1,92 @@
-use crate::AppMessage;
+use crate::{
+    plugins::{
+        events::ManagementEvents,
+        network::{is_valid_position, MAX_MUTE_DURATION},
+    },
+    AppMessage, Registration,
+};
 use axum::{
-    extract::Path,
-    response::IntoResponse,
-    routing::{delete, get},
+    extract::{Path, Query, Request, State},
+    http::{header, StatusCode},
+    middleware::{self, Next},
+    response::{
+        sse::{Event, KeepAlive, Sse},
+        IntoResponse, Response,
+    },
+    routing::{delete, get, post, put},
     Extension, Json, Router,
 };
-use models::server::api::{PlayerResponse, ServerInfoResponse};
-use tokio::sync::mpsc;
+use bevy::math::Vec3;
+use engine::{api_client::update_server, models::network::KickReason};
+use futures::{stream, Stream};
+use models::{
+    api::servers::UpdateServer,
+    data::tokens::constant_time_eq,
+    server::api::{
+        Ban, Broadcast, ChatLogEntry, ChatLogQuery, ChatStats, Mute, NewBan, PlayerDetails,
+        PlayerResponse, RenameServer, ServerInfoResponse, SetWhitelistEnabled, Teleport, Whitelist,
+    },
+};
+use serde::Deserialize;
+use std::{sync::Arc, time::Duration};
+use tokio::sync::{broadcast::error::RecvError, mpsc};
 use uuid::Uuid;
 
-pub fn create_router(tx: mpsc::Sender<AppMessage>) -> Router {
+pub fn create_router(
+    tx: mpsc::Sender<AppMessage>,
+    events: ManagementEvents,
+    registration: Registration,
+    admin_token: String,
+) -> Router {
     Router::new()
-        .route("/", get(get_server))
+        .route("/", get(get_server).patch(rename_server))
+        .route("/broadcast", post(broadcast))
+        .route("/events", get(stream_events))
         .route("/players", get(get_players))
-        .route("/players/:id", delete(kick_player))
+        .route("/players/:id", get(get_player).delete(kick_player))
+        .route("/players/:id/teleport", post(teleport_player))
+        .route("/players/:id/mute", post(mute_player).delete(unmute_player))
+        .route("/bans", get(list_bans).post(ban_player))
+        .route("/bans/:id", delete(unban_player))
+        .route(
+            "/whitelist",
+            get(get_whitelist).patch(set_whitelist_enabled),
+        )
+        .route(
+            "/whitelist/:id",
+            put(add_to_whitelist).delete(remove_from_whitelist),
+        )
+        .route("/chat/log", get(get_chat_log))
+        .route("/chat/stats", get(get_chat_stats))
+        .route("/ops", get(list_ops))
+        .route("/ops/:id", put(add_op).delete(remove_op))
         .layer(Extension(tx))
+        .layer(Extension(events))
+        .layer(Extension(Arc::new(registration)))
+        .layer(middleware::from_fn_with_state(
+            Arc::new(admin_token),
+            require_admin_token,
+        ))
+}
+
+/// Rejects every request that doesn't carry the admin token as a bearer token.
+async fn require_admin_token(
+    State(admin_token): State<Arc<String>>,
+    request: Request,
+    next: Next,
+) -> Result<Response, StatusCode> {
+    let token = request
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+
+    match token {
+        Some(token) if constant_time_eq(token.as_bytes(), admin_token.as_bytes()) => {
+            Ok(next.run(request).await)
+        }
+        _ => Err(StatusCode::UNAUTHORIZED),
+    }
 }
 
 #[axum::debug_handler]
@@ -36,21 +108,333 @@ async fn get_players(Extension(tx): Extension<mpsc::Sender<AppMessage>>) -> Json
 
     tx.send(AppMessage::GetPlayers(resp_tx)).await.unwrap();
 
-    let response = resp_rx.await.unwrap();
+    let players = resp_rx.await.unwrap();
+
+    Json(PlayerResponse { players })
+}
+
+#[axum::debug_handler]
+async fn get_player(
+    Path(id): Path<Uuid>,
+    Extension(tx): Extension<mpsc::Sender<AppMessage>>,
+) -> Result<Json<PlayerDetails>, StatusCode> {
+    let (resp_tx, resp_rx) = tokio::sync::oneshot::channel();
+
+    tx.send(AppMessage::GetPlayer(id, resp_tx)).await.unwrap();
+
+    resp_rx
+        .await
+        .unwrap()
+        .map(Json)
+        .ok_or(StatusCode::NOT_FOUND)
+}
+
+#[axum::debug_handler]
+async fn rename_server(
+    Extension(tx): Extension<mpsc::Sender<AppMessage>>,
+    Extension(registration): Extension<Arc<Registration>>,
+    Json(payload): Json<RenameServer>,
+) -> Result<Json<ServerInfoResponse>, StatusCode> {
+    let name = payload.name.trim();
+
+    if name.is_empty() {
+        return Err(StatusCode::BAD_REQUEST);
+    }
 
-    let players = PlayerResponse {
-        players: response.into_iter().map(|(id, _)| id).collect(),
-    };
+    let server = update_server(
+        &registration.api_base_url,
+        &registration.api_key,
+        &registration.id,
+        &UpdateServer {
+            addr: registration.addr,
+            port: registration.port,
+            name: name.to_string(),
+        },
+    )
+    .await
+    .map_err(|err| {
+        tracing::error!("Could not re-announce the server: {err}");
+        StatusCode::BAD_GATEWAY
+    })?;
 
-    Json(players)
+    tx.send(AppMessage::Rename(Box::new(server.clone())))
+        .await
+        .unwrap();
+
+    Ok(Json(ServerInfoResponse {
+        server: Some(server),
+    }))
+}
+
+#[axum::debug_handler]
+async fn broadcast(
+    Extension(tx): Extension<mpsc::Sender<AppMessage>>,
+    Json(payload): Json<Broadcast>,
+) -> StatusCode {
+    tx.send(AppMessage::Broadcast(payload.message))
+        .await
+        .unwrap();
+
+    StatusCode::NO_CONTENT
+}
+
+#[axum::debug_handler]
+async fn teleport_player(
+    Path(id): Path<Uuid>,
+    Extension(tx): Extension<mpsc::Sender<AppMessage>>,
+    Json(payload): Json<Teleport>,
+) -> StatusCode {
+    let (resp_tx, resp_rx) = tokio::sync::oneshot::channel();
+
+    // Values too large for an f32 come out of the JSON as infinity.
+    let position = Vec3::from_array(payload.position);
+    if !is_valid_position(position) {
+        return StatusCode::BAD_REQUEST;
+    }
+
+    tx.send(AppMessage::Teleport(id, position, resp_tx))
+        .await
+        .unwrap();
+
+    found(resp_rx.await.unwrap())
+}
+
+#[axum::debug_handler]
+async fn mute_player(
+    Path(id): Path<Uuid>,
+    Extension(tx): Extension<mpsc::Sender<AppMessage>>,
+    Json(payload): Json<Mute>,
+) -> StatusCode {
+    let (resp_tx, resp_rx) = tokio::sync::oneshot::channel();
+
+    let duration = payload.duration_secs.map(Duration::from_secs);
+    if duration.is_some_and(|duration| duration > MAX_MUTE_DURATION) {
+        return StatusCode::BAD_REQUEST;
+    }
+
+    tx.send(AppMessage::Mute(id, duration, resp_tx))
+        .await
+        .unwrap();
+
+    found(resp_rx.await.unwrap())
+}
+
+#[axum::debug_handler]
+async fn unmute_player(
+    Path(id): Path<Uuid>,
+    Extension(tx): Extension<mpsc::Sender<AppMessage>>,
+) -> StatusCode {
+    let (resp_tx, resp_rx) = tokio::sync::oneshot::channel();
+
+    tx.send(AppMessage::Unmute(id, resp_tx)).await.unwrap();
+
+    found(resp_rx.await.unwrap())
+}
+
+#[axum::debug_handler]
+async fn stream_events(
+    Extension(events): Extension<ManagementEvents>,
+) -> Sse<impl Stream<Item = Result<Event, axum::Error>>> {
+    let stream = stream::unfold(events.subscribe(), |mut rx| async move {
+        loop {
+            match rx.recv().await {
+                Ok(event) => return Some((Event::default().json_data(event), rx)),
+                Err(RecvError::Lagged(skipped)) => {
+                    tracing::warn!("Event subscriber lagged behind, skipped {skipped} events");
+                }
+                Err(RecvError::Closed) => return None,
+            }
+        }
+    });
+
+    Sse::new(stream).keep_alive(KeepAlive::default())
+}
+
+#[derive(Deserialize)]
+struct KickQuery {
+    reason: Option<String>,
 }
 
 #[axum::debug_handler]
 async fn kick_player(
     Path(id): Path<Uuid>,
+    Query(query): Query<KickQuery>,
     Extension(tx): Extension<mpsc::Sender<AppMessage>>,
 ) -> impl IntoResponse {
-    tx.send(AppMessage::KickPlayer(id)).await.unwrap();
+    tx.send(AppMessage::KickPlayer {
+        id,
+        reason: KickReason::Kicked {
+            reason: query.reason,
+        },
+    })
+    .await
+    .unwrap();
 
     "Ok"
 }
+
+#[axum::debug_handler]
+async fn list_bans(Extension(tx): Extension<mpsc::Sender<AppMessage>>) -> Json<Vec<Ban>> {
+    let (resp_tx, resp_rx) = tokio::sync::oneshot::channel();
+
+    tx.send(AppMessage::GetBans(resp_tx)).await.unwrap();
+
+    Json(resp_rx.await.unwrap())
+}
+
+#[axum::debug_handler]
+async fn ban_player(
+    Extension(tx): Extension<mpsc::Sender<AppMessage>>,
+    Json(new_ban): Json<NewBan>,
+) -> Result<Json<Ban>, StatusCode> {
+    let (resp_tx, resp_rx) = tokio::sync::oneshot::channel();
+
+    tx.send(AppMessage::Ban(new_ban, resp_tx)).await.unwrap();
+
+    let ban = resp_rx.await.unwrap().map_err(internal_error)?;
+
+    Ok(Json(ban))
+}
+
+#[axum::debug_handler]
+async fn unban_player(
+    Path(id): Path<Uuid>,
+    Extension(tx): Extension<mpsc::Sender<AppMessage>>,
+) -> Result<StatusCode, StatusCode> {
+    let (resp_tx, resp_rx) = tokio::sync::oneshot::channel();
+
+    tx.send(AppMessage::Unban(id, resp_tx)).await.unwrap();
+
+    match resp_rx.await.unwrap().map_err(internal_error)? {
+        true => Ok(StatusCode::NO_CONTENT),
+        false => Err(StatusCode::NOT_FOUND),
+    }
+}
+
+#[axum::debug_handler]
+async fn get_whitelist(Extension(tx): Extension<mpsc::Sender<AppMessage>>) -> Json<Whitelist> {
+    let (resp_tx, resp_rx) = tokio::sync::oneshot::channel();
+
+    tx.send(AppMessage::GetWhitelist(resp_tx)).await.unwrap();
+
+    Json(resp_rx.await.unwrap())
+}
+
+#[axum::debug_handler]
+async fn set_whitelist_enabled(
+    Extension(tx): Extension<mpsc::Sender<AppMessage>>,
+    Json(payload): Json<SetWhitelistEnabled>,
+) -> Result<StatusCode, StatusCode> {
+    let (resp_tx, resp_rx) = tokio::sync::oneshot::channel();
+
+    tx.send(AppMessage::SetWhitelistEnabled(payload.enabled, resp_tx))
+        .await
+        .unwrap();
+
+    resp_rx.await.unwrap().map_err(internal_error)?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[axum::debug_handler]
+async fn add_to_whitelist(
+    Path(id): Path<Uuid>,
+    Extension(tx): Extension<mpsc::Sender<AppMessage>>,
+) -> Result<StatusCode, StatusCode> {
+    let (resp_tx, resp_rx) = tokio::sync::oneshot::channel();
+
+    tx.send(AppMessage::AddToWhitelist(id, resp_tx))
+        .await
+        .unwrap();
+
+    resp_rx.await.unwrap().map_err(internal_error)?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[axum::debug_handler]
+async fn remove_from_whitelist(
+    Path(id): Path<Uuid>,
+    Extension(tx): Extension<mpsc::Sender<AppMessage>>,
+) -> Result<StatusCode, StatusCode> {
+    let (resp_tx, resp_rx) = tokio::sync::oneshot::channel();
+
+    tx.send(AppMessage::RemoveFromWhitelist(id, resp_tx))
+        .await
+        .unwrap();
+
+    match resp_rx.await.unwrap().map_err(internal_error)? {
+        true => Ok(StatusCode::NO_CONTENT),
+        false => Err(StatusCode::NOT_FOUND),
+    }
+}
+
+#[axum::debug_handler]
+async fn get_chat_log(
+    Query(query): Query<ChatLogQuery>,
+    Extension(tx): Extension<mpsc::Sender<AppMessage>>,
+) -> Json<Vec<ChatLogEntry>> {
+    let (resp_tx, resp_rx) = tokio::sync::oneshot::channel();
+
+    tx.send(AppMessage::GetChatLog(query, resp_tx))
+        .await
+        .unwrap();
+
+    Json(resp_rx.await.unwrap())
+}
+
+#[axum::debug_handler]
+async fn get_chat_stats(Extension(tx): Extension<mpsc::Sender<AppMessage>>) -> Json<ChatStats> {
+    let (resp_tx, resp_rx) = tokio::sync::oneshot::channel();
+
+    tx.send(AppMessage::GetChatStats(resp_tx)).await.unwrap();
+
+    Json(resp_rx.await.unwrap())
+}
+
+#[axum::debug_handler]
+async fn list_ops(Extension(tx): Extension<mpsc::Sender<AppMessage>>) -> Json<Vec<Uuid>> {
+    let (resp_tx, resp_rx) = tokio::sync::oneshot::channel();
+
+    tx.send(AppMessage::GetOps(resp_tx)).await.unwrap();
+
+    Json(resp_rx.await.unwrap())
+}
+
+#[axum::debug_handler]
+async fn add_op(
+    Path(id): Path<Uuid>,
+    Extension(tx): Extension<mpsc::Sender<AppMessage>>,
+) -> Result<StatusCode, StatusCode> {
+    let (resp_tx, resp_rx) = tokio::sync::oneshot::channel();
+
+    tx.send(AppMessage::AddOp(id, resp_tx)).await.unwrap();
+
+    resp_rx.await.unwrap().map_err(internal_error)?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[axum::debug_handler]
+async fn remove_op(
+    Path(id): Path<Uuid>,
+    Extension(tx): Extension<mpsc::Sender<AppMessage>>,
+) -> Result<StatusCode, StatusCode> {
+    let (resp_tx, resp_rx) = tokio::sync::oneshot::channel();
+
+    tx.send(AppMessage::RemoveOp(id, resp_tx)).await.unwrap();
+
+    Ok(found(resp_rx.await.unwrap().map_err(internal_error)?))
+}
+
+fn found(found: bool) -> StatusCode {
+    match found {
+        true => StatusCode::NO_CONTENT,
+        false => StatusCode::NOT_FOUND,
+    }
+}
+
+fn internal_error(err: anyhow::Error) -> StatusCode {
+    tracing::error!("Could not update the access list: {err}");
+    StatusCode::INTERNAL_SERVER_ERROR
+}